@@ -0,0 +1,109 @@
+//! 通道校准
+//!
+//! 校准流程：输入参考电压电流，采集各从站读数，可以换不同的参考值多次采集，
+//! 最后按通道拟合增益和偏移并保存。
+
+use std::{collections::BTreeMap, time::Duration};
+
+use mb::{
+    units::{Amps, Volts},
+    utils::current_timestamp,
+    voltage::{Calibration, VoltageData},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::device::device_key;
+
+/// 从站各通道的校准系数
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CalibrationRecord {
+    pub port: String,
+    pub slave: u8,
+    /// 按通道顺序
+    pub channels: Vec<Calibration>,
+    pub calibrated_at: Duration,
+}
+
+impl CalibrationRecord {
+    /// 数据库键: 端口#从站
+    pub fn key(&self) -> String {
+        device_key(&self.port, self.slave)
+    }
+}
+
+/// 单个通道的 (读数, 参考值)，电压 V，电流 A
+#[derive(Debug, Clone, Default)]
+struct Samples {
+    voltage: Vec<(f32, f32)>,
+    current: Vec<(f32, f32)>,
+}
+
+/// 校准采集
+#[derive(Debug, Clone, Default)]
+pub struct CalibrationSession {
+    port: String,
+    samples: BTreeMap<(u8, usize), Samples>,
+}
+
+impl CalibrationSession {
+    pub fn new<T: Into<String>>(port: T) -> Self {
+        Self {
+            port: port.into(),
+            samples: BTreeMap::new(),
+        }
+    }
+
+    /// 记录一次读数，使用校准前的值；没有参考值的一项不参与拟合
+    pub fn capture(&mut self, data: &VoltageData, voltage: Option<Volts>, current: Option<Amps>) {
+        for (position, ch) in data.data.iter().enumerate() {
+            let samples = self.samples.entry((data.slave, position)).or_default();
+            if let Some(voltage) = voltage {
+                samples
+                    .voltage
+                    .push((ch.raw_voltage.value(), voltage.value()));
+            }
+            if let Some(current) = current {
+                samples
+                    .current
+                    .push((ch.raw_current.value(), current.value()));
+            }
+        }
+    }
+
+    /// 已采集的次数
+    pub fn count(&self) -> usize {
+        self.samples
+            .values()
+            .map(|s| s.voltage.len().max(s.current.len()))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// 按从站拟合，无法拟合的通道使用默认系数
+    pub fn finish(&self) -> Vec<CalibrationRecord> {
+        let mut records: BTreeMap<u8, CalibrationRecord> = BTreeMap::new();
+        for (&(slave, position), samples) in &self.samples {
+            let record = records.entry(slave).or_insert_with(|| CalibrationRecord {
+                port: self.port.clone(),
+                slave,
+                channels: Vec::new(),
+                calibrated_at: current_timestamp(),
+            });
+
+            let mut calibration = Calibration::default();
+            if let Some((gain, offset)) = Calibration::fit(&samples.voltage) {
+                calibration.voltage_gain = gain;
+                calibration.voltage_offset = Volts::new(offset);
+            }
+            if let Some((gain, offset)) = Calibration::fit(&samples.current) {
+                calibration.current_gain = gain;
+                calibration.current_offset = Amps::new(offset);
+            }
+
+            // 按通道顺序遍历，中间缺少的通道使用默认系数
+            record.channels.resize(position, Calibration::default());
+            record.channels.push(calibration);
+        }
+        records.into_values().collect()
+    }
+}
@@ -1,4 +1,10 @@
-use mb::{protocol::default_port_name, voltage::Verify};
+use mb::{
+    policy::Policy,
+    power::PowerFeatures,
+    protocol::{Framing, default_port_name},
+    register_map::WordOrder,
+    voltage::{Verify, VoltageLayout},
+};
 use serde::{Deserialize, Serialize};
 
 use crate::dirs;
@@ -26,6 +32,10 @@ pub struct Config {
 
     /// 历史数据
     pub history: HistoryConfig,
+
+    /// 记录全部端口的收发报文到 [`dirs::capture_file`]
+    #[serde(default)]
+    pub capture: bool,
 }
 
 // 端口
@@ -34,6 +44,12 @@ pub struct SerialPortConfig {
     pub name: String,
     pub port: String,       // com or tty
     pub baudrate: Baudrate, //
+    /// 帧格式
+    #[serde(default)]
+    pub framing: Framing,
+    /// 超时、重试与请求间隔
+    #[serde(default)]
+    pub policy: Policy,
 }
 
 impl Default for SerialPortConfig {
@@ -42,6 +58,8 @@ impl Default for SerialPortConfig {
             name: String::default(),
             port: default_port_name(),
             baudrate: Baudrate::default(),
+            framing: Framing::default(),
+            policy: Policy::default(),
         }
     }
 }
@@ -58,6 +76,10 @@ pub struct VoltageConfig {
 
     // 验证
     pub verify: Verify,
+
+    /// 通道数、起始寄存器、比例和单位
+    #[serde(default)]
+    pub layout: VoltageLayout,
 }
 
 // 温度
@@ -82,6 +104,12 @@ pub struct PowerConfig {
     pub name: String,
     pub serial_port: SerialPortConfig,
     pub slave: u8,
+    /// 电压电流浮点数的字序
+    #[serde(default)]
+    pub word_order: WordOrder,
+    /// 电源支持的可选寄存器，按照电源手册开启
+    #[serde(default)]
+    pub features: PowerFeatures,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
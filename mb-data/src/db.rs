@@ -1,9 +1,11 @@
 use std::sync::{Mutex, OnceLock};
 
-use mb::Result;
+use crate::error::Result;
 use redb::{Database, TableDefinition};
 
+pub mod calibration;
 pub mod config;
+pub mod device;
 pub mod task;
 pub mod user;
 pub mod voltage;
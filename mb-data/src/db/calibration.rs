@@ -0,0 +1,128 @@
+use crate::error::Result;
+use redb::{Database, ReadableTable, TableDefinition, TableError};
+
+use crate::{calibration::CalibrationRecord, device::device_key, error::Error};
+
+pub const TABLE: TableDefinition<String, &[u8]> = TableDefinition::new("calibration");
+
+pub struct TableCalibration;
+
+impl TableCalibration {
+    pub fn set(db: &Database, data: &CalibrationRecord) -> Result<()> {
+        let write_txn = db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            let value = serde_json::to_vec(data)?;
+            table.insert(data.key(), value.as_slice())?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    pub fn get(db: &Database, port: &str, slave: u8) -> Result<CalibrationRecord> {
+        let read_txn = db.begin_read()?;
+        let table = match read_txn.open_table(TABLE) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Error::DbNone.into(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let query = table.get(device_key(port, slave))?;
+        let data = match query {
+            Some(value) => {
+                let data: CalibrationRecord = serde_json::from_slice(value.value())?;
+                data
+            }
+            None => return Error::DbNone.into(),
+        };
+
+        Ok(data)
+    }
+
+    pub fn delete(db: &Database, port: &str, slave: u8) -> Result<()> {
+        let write_txn = db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            table.remove(device_key(port, slave))?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    /// 全部记录，从未校准过时为空
+    pub fn list(db: &Database) -> Result<Vec<CalibrationRecord>> {
+        let read_txn = db.begin_read()?;
+        let table = match read_txn.open_table(TABLE) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut records = Vec::new();
+        for entry in table.iter()? {
+            let (_, value) = entry?;
+            let record: CalibrationRecord = serde_json::from_slice(value.value())?;
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mb::{
+        units::{Amps, Volts},
+        voltage::{VoltageChannel, VoltageData},
+    };
+    use redb::{Database, backends::InMemoryBackend};
+
+    use super::TableCalibration;
+    use crate::{calibration::CalibrationSession, error::Error};
+
+    #[test]
+    fn calibrate_and_save() {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        // 没有表时与没有记录一样，调用方据此区分未校准和读取失败
+        let err = TableCalibration::get(&db, "COM1", 5).unwrap_err();
+        assert!(matches!(err, Error::DbNone), "{err}");
+
+        // 通道 0 读数偏高 2%，通道 1 偏移 0.1V
+        let reading = |voltage: f32| {
+            let data = vec![
+                VoltageChannel::new(0, Volts::new(voltage * 1.02), Amps::new(1.0)),
+                VoltageChannel::new(1, Volts::new(voltage + 0.1), Amps::new(1.0)),
+            ];
+            VoltageData::new(Default::default(), 5, data)
+        };
+        let mut session = CalibrationSession::new("COM1");
+        session.capture(&reading(5.0), Some(Volts::new(5.0)), None);
+        session.capture(&reading(20.0), Some(Volts::new(20.0)), None);
+        assert_eq!(2, session.count());
+
+        let records = session.finish();
+        assert_eq!(1, records.len());
+        TableCalibration::set(&db, &records[0]).unwrap();
+        let err = TableCalibration::get(&db, "COM1", 6).unwrap_err();
+        assert!(matches!(err, Error::DbNone), "{err}");
+
+        let record = TableCalibration::get(&db, "COM1", 5).unwrap();
+        let mut data = reading(12.0);
+        data.calibrate(&record.channels);
+        for ch in &data.data {
+            assert!(
+                (ch.voltage - Volts::new(12.0)).abs() < Volts::new(1e-3),
+                "{ch:?}"
+            );
+            assert_eq!(Amps::new(1.0), ch.current);
+        }
+        assert_eq!(Volts::new(12.1), data.data[1].raw_voltage);
+
+        TableCalibration::delete(&db, "COM1", 5).unwrap();
+        assert!(TableCalibration::list(&db).unwrap().is_empty());
+    }
+}
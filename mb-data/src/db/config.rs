@@ -1,4 +1,4 @@
-use mb::Result;
+use crate::error::Result;
 use redb::{Database, TableDefinition};
 
 use crate::config::Config;
@@ -0,0 +1,113 @@
+use crate::error::Result;
+use redb::{Database, ReadableTable, TableDefinition, TableError};
+
+use crate::{
+    device::{DeviceRecord, device_key},
+    error::Error,
+};
+
+pub const TABLE: TableDefinition<String, &[u8]> = TableDefinition::new("device");
+
+pub struct TableDevice;
+
+impl TableDevice {
+    pub fn set(db: &Database, data: &DeviceRecord) -> Result<()> {
+        let write_txn = db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            let value = serde_json::to_vec(data)?;
+            table.insert(data.key(), value.as_slice())?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    pub fn get(db: &Database, port: &str, slave: u8) -> Result<DeviceRecord> {
+        let read_txn = db.begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+
+        let query = table.get(device_key(port, slave))?;
+        let data = match query {
+            Some(value) => {
+                let data: DeviceRecord = serde_json::from_slice(value.value())?;
+                data
+            }
+            None => return Error::DbNone.into(),
+        };
+
+        Ok(data)
+    }
+
+    pub fn delete(db: &Database, port: &str, slave: u8) -> Result<()> {
+        let write_txn = db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            table.remove(device_key(port, slave))?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    /// 全部记录，从未探测过时为空
+    pub fn list(db: &Database) -> Result<Vec<DeviceRecord>> {
+        let read_txn = db.begin_read()?;
+        let table = match read_txn.open_table(TABLE) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut devices = Vec::new();
+        for entry in table.iter()? {
+            let (_, value) = entry?;
+            let device: DeviceRecord = serde_json::from_slice(value.value())?;
+            devices.push(device);
+        }
+
+        Ok(devices)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use mb::diagnostics::DeviceInfo;
+    use redb::{Database, backends::InMemoryBackend};
+
+    use super::TableDevice;
+    use crate::device::DeviceRecord;
+
+    #[test]
+    fn set_and_list() {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        assert!(TableDevice::list(&db).unwrap().is_empty());
+
+        let info = DeviceInfo {
+            time: Default::default(),
+            slave: 5,
+            objects: BTreeMap::from([
+                (0x00, "mock".to_string()),
+                (0x01, "MB-05".to_string()),
+                (0x02, "v1.0.0".to_string()),
+            ]),
+            more_follows: false,
+            next_object: 0,
+        };
+        TableDevice::set(&db, &DeviceRecord::new("COM1", &info)).unwrap();
+
+        let record = TableDevice::get(&db, "COM1", 5).unwrap();
+        assert_eq!(
+            ("mock", "MB-05"),
+            (record.vendor.as_str(), record.model.as_str())
+        );
+        assert_eq!(1, TableDevice::list(&db).unwrap().len());
+
+        TableDevice::delete(&db, "COM1", 5).unwrap();
+        assert!(TableDevice::get(&db, "COM1", 5).is_err());
+    }
+}
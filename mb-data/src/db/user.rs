@@ -1,4 +1,4 @@
-use mb::Result;
+use crate::error::Result;
 use redb::{Database, ReadableTable, TableDefinition};
 
 use crate::{error::Error, user::UserConfig};
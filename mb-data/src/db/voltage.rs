@@ -2,8 +2,9 @@ use std::collections::HashMap;
 use std::ops::Sub;
 use std::time::Duration;
 
+use crate::error::Result;
+use mb::units::{Amps, Celsius, Volts};
 use mb::voltage::{Verify, VoltageChannel, VoltageState};
-use mb::Result;
 use mb::{utils::current_timestamp, voltage::VoltageData};
 use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
 use serde::{Deserialize, Serialize};
@@ -23,29 +24,22 @@ pub struct VoltageDataGroup {
     pub task_name: String,
     pub start_at: Duration,
     pub task_age_time: Duration,
-    pub temperature: f32,
+    /// 温度，读取失败时为空
+    pub temperature: Option<Celsius>,
     pub data: Vec<VoltageData>,
 }
 
 impl VoltageDataGroup {
-    pub fn voltage(&self) -> f32 {
+    pub fn voltage(&self) -> Volts {
         let l = self.data.len();
 
-        self.data
-            .iter()
-            .map(|c| c.voltage())
-            .fold(0_f32, |s, a| s + a)
-            / l as f32
+        self.data.iter().map(|c| c.voltage()).sum::<Volts>() / l as f32
     }
 
-    pub fn current(&self) -> f32 {
+    pub fn current(&self) -> Amps {
         let l = self.data.len();
 
-        self.data
-            .iter()
-            .map(|c| c.current())
-            .fold(0_f32, |s, a| s + a)
-            / l as f32
+        self.data.iter().map(|c| c.current()).sum::<Amps>() / l as f32
     }
 }
 
@@ -309,23 +303,22 @@ pub fn check_defective_in_secs(
                 VoltageChannelItem {
                     index: 0,
                     time: Duration::from_secs(0),
-                    ch: VoltageChannel {
-                        index: 0,
-                        voltage: 0.,
-                        current: 0.,
-                        state: Default::default(),
-                    },
+                    ch: VoltageChannel::default(),
                 },
                 |mut a, b| {
                     a.index = b.index;
                     a.ch.index = b.ch.index;
                     a.ch.voltage += b.ch.voltage;
                     a.ch.current += b.ch.current;
+                    a.ch.raw_voltage += b.ch.raw_voltage;
+                    a.ch.raw_current += b.ch.raw_current;
                     a
                 },
             );
             item.ch.voltage /= l as f32;
             item.ch.current /= l as f32;
+            item.ch.raw_voltage /= l as f32;
+            item.ch.raw_current /= l as f32;
             item.ch.set_state(verify);
 
             item
@@ -431,23 +424,22 @@ pub fn voltage_average_every_n_minutes(
                         VoltageChannelItem {
                             index: 0,
                             time: Duration::from_secs(key),
-                            ch: VoltageChannel {
-                                index: 0,
-                                voltage: 0.,
-                                current: 0.,
-                                state: Default::default(),
-                            },
+                            ch: VoltageChannel::default(),
                         },
                         |mut a, b| {
                             a.index = b.index;
                             a.ch.index = b.ch.index;
                             a.ch.voltage += b.ch.voltage;
                             a.ch.current += b.ch.current;
+                            a.ch.raw_voltage += b.ch.raw_voltage;
+                            a.ch.raw_current += b.ch.raw_current;
                             a
                         },
                     );
                     item.ch.voltage /= l as f32;
                     item.ch.current /= l as f32;
+                    item.ch.raw_voltage /= l as f32;
+                    item.ch.raw_current /= l as f32;
 
                     item
                 })
@@ -467,6 +459,7 @@ pub fn voltage_average_every_n_minutes(
 #[cfg(test)]
 mod test {
     use mb::{
+        units::Celsius,
         utils::current_timestamp,
         voltage::{VoltageChannel, VoltageData},
     };
@@ -490,7 +483,7 @@ mod test {
             task_name: "test".into(),
             start_at: dur,
             task_age_time: dur,
-            temperature: 30.0,
+            temperature: Some(Celsius::new(30.0)),
             data: vec![VoltageData {
                 time: dur,
                 slave: 100,
@@ -0,0 +1,39 @@
+//! 设备标识记录
+
+use std::time::Duration;
+
+use mb::{diagnostics::DeviceInfo, utils::current_timestamp};
+use serde::{Deserialize, Serialize};
+
+/// 从站的厂商、型号与固件版本，设置界面探测后保存
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeviceRecord {
+    pub port: String,
+    pub slave: u8,
+    pub vendor: String,
+    pub model: String,
+    pub revision: String,
+    pub probed_at: Duration,
+}
+
+impl DeviceRecord {
+    pub fn new<T: Into<String>>(port: T, info: &DeviceInfo) -> Self {
+        Self {
+            port: port.into(),
+            slave: info.slave,
+            vendor: info.vendor().unwrap_or_default().into(),
+            model: info.model().unwrap_or_default().into(),
+            revision: info.revision().unwrap_or_default().into(),
+            probed_at: current_timestamp(),
+        }
+    }
+
+    /// 数据库键: 端口#从站
+    pub fn key(&self) -> String {
+        device_key(&self.port, self.slave)
+    }
+}
+
+pub fn device_key(port: &str, slave: u8) -> String {
+    format!("{port}#{slave}")
+}
@@ -1,7 +1,7 @@
 use std::{fs, path::PathBuf};
 
 use etcetera::{app_strategy::choose_native_strategy, AppStrategy, AppStrategyArgs};
-use mb::Result;
+use crate::error::Result;
 
 fn base_dir() -> Result<impl AppStrategy> {
     Ok(choose_native_strategy(AppStrategyArgs {
@@ -37,3 +37,13 @@ pub fn log_file() -> PathBuf {
     }
     cache_dir.join("mbreader.log")
 }
+
+/// 收发报文记录，JSON lines
+pub fn capture_file() -> PathBuf {
+    let strategy = base_dir().unwrap();
+    let cache_dir = strategy.cache_dir();
+    if !cache_dir.exists() {
+        fs::create_dir_all(cache_dir.clone()).unwrap();
+    }
+    cache_dir.join("capture.jsonl")
+}
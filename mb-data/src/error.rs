@@ -1,5 +1,7 @@
 use std::num::ParseIntError;
 
+pub type Result<T> = std::result::Result<T, Error>;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error(transparent)]
@@ -35,6 +37,15 @@ pub enum Error {
     #[error(transparent)]
     Prase(#[from] ParseIntError),
 
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Dirs(#[from] etcetera::HomeDirError),
+
+    #[error(transparent)]
+    Mb(#[from] mb::error::Error),
+
     #[error("没有对应的数据")]
     DbNone,
 
@@ -51,8 +62,8 @@ pub enum Error {
     Fail,
 }
 
-impl<T> From<Error> for mb::Result<T> {
+impl<T> From<Error> for Result<T> {
     fn from(val: Error) -> Self {
-        Err(Box::new(val))
+        Err(val)
     }
 }
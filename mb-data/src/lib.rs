@@ -1,5 +1,10 @@
+// redb 的错误类型较大，数据库操作不在热路径上，不做装箱
+#![allow(clippy::result_large_err)]
+
+pub mod calibration;
 pub mod config;
 pub mod db;
+pub mod device;
 pub mod dirs;
 pub mod error;
 pub mod task;
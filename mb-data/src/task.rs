@@ -1,7 +1,10 @@
 //! task 序列，任务列表
 use std::time::Duration;
 
-use mb::voltage::Verify;
+use mb::{
+    units::{Amps, Volts},
+    voltage::Verify,
+};
 use serde::{Deserialize, Serialize};
 
 /// 序列
@@ -55,8 +58,8 @@ impl AB {
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct PowerConfig {
     pub mode: PowerMode,
-    pub voltage: u32,
-    pub current: u32,
+    pub voltage: Volts,
+    pub current: Amps,
 }
 
 #[derive(
@@ -68,12 +71,21 @@ pub enum PowerMode {
     Dc,
 }
 
+impl From<PowerMode> for mb::power::OutputMode {
+    fn from(mode: PowerMode) -> Self {
+        match mode {
+            PowerMode::Ac => mb::power::OutputMode::Ac,
+            PowerMode::Dc => mb::power::OutputMode::Dc,
+        }
+    }
+}
+
 // 任务内容
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct TaskItem {
     pub index: usize,
     pub power_on: bool,
-    pub voltage: u32,
+    pub voltage: Volts,
     pub dur: Duration,
 }
 
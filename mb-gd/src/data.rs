@@ -59,8 +59,8 @@ impl ToGodotVia for VoltageChannel {
     fn to_godot(&self) -> Self::Via {
         let mut dict = Dictionary::new();
         dict.set("index", format!("{:2}", self.index + 1));
-        dict.set("voltage", format!("{:.2}V", self.voltage));
-        dict.set("current", format!("{:2} mA", self.current));
+        dict.set("voltage", format!("{:.2}", self.voltage));
+        dict.set("current", format!("{:2} mA", self.current.milli()));
         dict
     }
 }
@@ -1,5 +1,7 @@
 use std::num::ParseIntError;
 
+pub type Result<T> = std::result::Result<T, Error>;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error(transparent)]
@@ -11,6 +13,18 @@ pub enum Error {
     #[error(transparent)]
     Prase(#[from] ParseIntError),
 
+    #[error(transparent)]
+    Mb(#[from] mb::error::Error),
+
+    #[error(transparent)]
+    Data(#[from] mb_data::error::Error),
+
+    #[error(transparent)]
+    Xlsx(#[from] rust_xlsxwriter::XlsxError),
+
+    #[error(transparent)]
+    Log(#[from] log::SetLoggerError),
+
     #[error("没有对应的数据")]
     DbNone,
 
@@ -30,8 +44,8 @@ pub enum Error {
     Fail,
 }
 
-impl<T> From<Error> for mb::Result<T> {
+impl<T> From<Error> for Result<T> {
     fn from(val: Error) -> Self {
-        Err(Box::new(val))
+        Err(val)
     }
 }
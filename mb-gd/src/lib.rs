@@ -38,6 +38,9 @@ unsafe impl ExtensionLibrary for MyExtension {
             // 手动注销
             engine.unregister_singleton(&my_global_name);
             singleton.free();
+
+            // 关闭全部串口
+            mb::connection::close_all();
         }
     }
 }
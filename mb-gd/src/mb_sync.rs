@@ -1,124 +1,229 @@
-use mb::power::{Power, PowerData, PowerMode};
-use mb::protocol::Builder;
-use mb::relay::{Relay, RelayData, RelayMode};
-use mb::temperature::{Temperature, TemperatureData, TemperatureMode};
-use mb::voltage::{Voltage, VoltageData};
-use mb::Result;
+use std::sync::Mutex;
 
-use mb_data::config::{RelayConfig, TemperatureConfig, VoltageConfig};
+use mb::Result;
+use mb::capture::Capture;
+use mb::device::Device;
+use mb::diagnostics::{DeviceIdCode, DeviceInfo, Diagnostics, DiagnosticsMode, DiagnosticsReply};
+use mb::error::Error as MbError;
+use mb::power::{OutputMode, Power, PowerCommand, PowerData, PowerMode, PowerReply, PowerStatus};
+use mb::protocol::{Builder, FunRequest};
+use mb::relay::{Relay, RelayData, RelayMode, RelayReply};
+use mb::scheduler::{Priority, submit};
+use mb::temperature::{
+    Temperature, TemperatureData, TemperatureMode, TemperatureReply, TemperatureStatus,
+};
+use mb::units::{Amps, Celsius, Volts};
+use mb::voltage::{Voltage, VoltageCommand, VoltageData, VoltageMode};
+
+use mb_data::{
+    config::{PowerConfig, RelayConfig, SerialPortConfig, TemperatureConfig, VoltageConfig},
+    db::{calibration::TableCalibration, get_db},
+    dirs::capture_file,
+    error::Error as DataError,
+};
 
 use crate::data::AB;
-use crate::error::Error;
 
-/// 获取电压电流
+/// 报文记录，全部端口共用一个文件
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+/// 开启或关闭报文记录
+pub fn set_capture(enable: bool) {
+    let mut capture = CAPTURE.lock().unwrap();
+    match (enable, capture.is_some()) {
+        (true, false) => match Capture::create(capture_file()) {
+            Ok(c) => *capture = Some(c),
+            Err(e) => log::error!("报文记录文件打开失败: {e}"),
+        },
+        (false, true) => *capture = None,
+        _ => {}
+    }
+}
+
+/// 按照端口配置创建 Builder，开启 `mock` 功能时测试端口直接连接模拟设备
+fn builder(config: &SerialPortConfig) -> Builder {
+    let builder = match config.port.as_str() {
+        #[cfg(feature = "mock")]
+        mb::protocol::TEST_PORT => {
+            Builder::with_transport(&config.port, mb_mock::loopback()).policy(config.policy)
+        }
+        port => Builder::new(port, config.baudrate.into())
+            .framing(config.framing)
+            .policy(config.policy),
+    };
+
+    match CAPTURE.lock().unwrap().clone() {
+        Some(capture) => builder.capture(capture),
+        None => builder,
+    }
+}
+
+/// 经由总线调度发送设备命令，写入命令优先于轮询
+fn execute<D: Device>(
+    config: &SerialPortConfig,
+    slave: u8,
+    command: &D::Command,
+) -> Result<D::Output> {
+    let requests = D::requests(slave, command);
+    let priority = requests
+        .iter()
+        .map(Priority::of)
+        .max()
+        .unwrap_or(Priority::Poll);
+    send::<D>(config, command, requests, priority)
+}
+
+/// 按照指定优先级发送设备命令
+fn execute_with<D: Device>(
+    config: &SerialPortConfig,
+    slave: u8,
+    command: &D::Command,
+    priority: Priority,
+) -> Result<D::Output> {
+    send::<D>(config, command, D::requests(slave, command), priority)
+}
+
+/// 依次发送命令的全部请求并解析响应
+///
+/// 从站返回异常时记录是哪个从站拒绝了哪个命令
+fn send<D: Device>(
+    config: &SerialPortConfig,
+    command: &D::Command,
+    requests: Vec<FunRequest>,
+    priority: Priority,
+) -> Result<D::Output> {
+    let builder = builder(config);
+    let mut responses = Vec::new();
+    for request in requests {
+        let response = submit(&builder, &request, priority)
+            .wait()
+            .inspect_err(|e| {
+                if let MbError::Exception { .. } = e {
+                    log::error!("{}: {e}", config.port);
+                }
+            })?;
+        responses.push(response);
+    }
+    D::decode_all(command, responses)
+}
+
+/// 获取电压电流，有校准系数时同时计算校准值，校准系数读取失败只记录日志
 pub fn get_voltage_data(config: &VoltageConfig, slave: u8) -> Result<VoltageData> {
-    let port_name = config.serial_port.port.clone();
-    let baudrate = config.serial_port.baudrate.into();
-    // let slave = config.voltage.salve_a;
+    let command = VoltageCommand::new(VoltageMode::Read, config.layout.clone())?;
+    let mut data = execute::<Voltage>(&config.serial_port, slave, &command)?;
 
-    let builder = Builder::new(port_name, baudrate);
-    let request = Voltage::request(slave);
-    let response = builder.call(&request)?;
-    response.try_into()
+    let calibration = {
+        let db = get_db().lock().unwrap();
+        TableCalibration::get(&db, &config.serial_port.port, slave)
+    };
+    match calibration {
+        Ok(record) => data.calibrate(&record.channels),
+        // 未校准的模块只有原始值
+        Err(DataError::DbNone) => {}
+        Err(e) => log::error!("{} {slave} 读取校准系数失败: {e}", config.serial_port.port),
+    }
+    Ok(data)
+}
+
+/// 读取电压模块的基本设备标识
+pub fn get_device_info(config: &VoltageConfig, slave: u8) -> Result<DeviceInfo> {
+    let mode = DiagnosticsMode::DeviceId(DeviceIdCode::Basic, 0x00);
+    match execute_with::<Diagnostics>(&config.serial_port, slave, &mode, Priority::Background)? {
+        DiagnosticsReply::DeviceInfo(info) => Ok(info),
+        _ => MbError::DataNull.into(),
+    }
 }
 
 /// 获取温度
 pub fn get_temperature(config: &TemperatureConfig, ab: AB) -> Result<TemperatureData> {
-    let port_name = config.serial_port.port.clone();
-    let baudrate = config.serial_port.baudrate.into();
-    let slave = config.slave;
-
     let mode = if ab.is_a() {
         TemperatureMode::Temp1
     } else {
         TemperatureMode::Temp2
     };
 
-    let builder = Builder::new(port_name, baudrate);
-    let request = Temperature::request(slave, &mode);
-    let response = builder.call(&request)?;
-    response.try_into()
+    match execute::<Temperature>(&config.serial_port, config.slave, &mode)? {
+        TemperatureReply::Value(data) => Ok(data),
+        _ => MbError::DataNull.into(),
+    }
 }
 
-/// 设置取温度
-pub fn set_temperature(config: &TemperatureConfig, ab: AB, temp: u16) -> Result<TemperatureData> {
-    let port_name = config.serial_port.port.clone();
-    let baudrate = config.serial_port.baudrate.into();
-    let slave = config.slave;
+/// 读取两路温度、设定温度、运行状态和按键
+pub fn get_temperature_status(config: &TemperatureConfig) -> Result<TemperatureStatus> {
+    match execute::<Temperature>(&config.serial_port, config.slave, &TemperatureMode::Status)? {
+        TemperatureReply::Status(status) => Ok(status),
+        _ => MbError::DataNull.into(),
+    }
+}
 
+/// 设置取温度
+pub fn set_temperature(config: &TemperatureConfig, ab: AB, temp: Celsius) -> Result<()> {
     let mode = if ab.is_a() {
         TemperatureMode::Set1(temp)
     } else {
         TemperatureMode::Set2(temp)
     };
 
-    let builder = Builder::new(port_name, baudrate);
-    let request = Temperature::request(slave, &mode);
-    let response = builder.call(&request)?;
-    response.try_into()
+    execute::<Temperature>(&config.serial_port, config.slave, &mode)?;
+    Ok(())
 }
 
 /// 获取继电器开关
-pub fn get_relay(config: &RelayConfig, ab: AB) -> Result<RelayData> {
-    let port_name = config.serial_port.port.clone();
-    let baudrate = config.serial_port.baudrate.into();
-    let slave = config.slave;
-
-    let builder = Builder::new(port_name, baudrate);
-    let request = Relay::request(slave, &RelayMode::Read);
-    let response = builder.call(&request)?;
-    response.try_into()
+pub fn get_relay(config: &RelayConfig) -> Result<RelayData> {
+    match execute::<Relay>(&config.serial_port, config.slave, &RelayMode::Read)? {
+        RelayReply::Value(data) => Ok(data),
+        RelayReply::Written => MbError::DataNull.into(),
+    }
 }
 
-/// 设定继电器
-pub fn set_relay(config: &RelayConfig, ab: AB, mode: &RelayMode) -> Result<()> {
-    let port_name = config.serial_port.port.clone();
-    let baudrate = config.serial_port.baudrate.into();
-    let slave = config.slave;
-
-    let builder = Builder::new(port_name, baudrate);
-    let request = Relay::request(slave, mode);
-    let response = builder.call(&request)?;
+/// 设定继电器，写入未确认时返回错误
+pub fn set_relay(config: &RelayConfig, mode: &RelayMode) -> Result<()> {
+    execute::<Relay>(&config.serial_port, config.slave, mode)?;
+    Ok(())
+}
 
-    if request == response {
-        return Ok(());
+/// 一次读取电源全部状态
+pub fn get_power_status(config: &PowerConfig) -> Result<PowerStatus> {
+    match set_power(config, &PowerMode::Status)? {
+        PowerReply::Status(status) => Ok(status),
+        _ => MbError::DataNull.into(),
     }
+}
 
-    Error::PortFail.into()
+/// 获取电源电压
+pub fn get_power_voltage(config: &PowerConfig) -> Result<PowerData> {
+    match set_power(config, &PowerMode::GetVoltage)? {
+        PowerReply::Value(data) => Ok(data),
+        _ => MbError::DataNull.into(),
+    }
 }
 
-/// 获取电源开关状态
-pub fn get_power_on(config: &RelayConfig, ab: AB) -> Result<PowerData> {
-    let port_name = config.serial_port.port.clone();
-    let baudrate = config.serial_port.baudrate.into();
-    let slave = config.slave;
+/// 按照任务设定输出模式、电压、电流后开启输出，没有输出模式寄存器的电源跳过模式设定
+pub fn power_on(
+    config: &PowerConfig,
+    mode: OutputMode,
+    voltage: Volts,
+    current: Amps,
+) -> Result<()> {
+    if config.features.output_mode {
+        set_power(config, &PowerMode::SetOutputMode(mode))?;
+    } else {
+        log::debug!("{} 未开启输出模式，跳过设定 {mode}", config.name);
+    }
+    set_power(config, &PowerMode::SetVoltage(voltage))?;
+    set_power(config, &PowerMode::SetCurrent(current))?;
+    set_power(config, &PowerMode::SetOutput(true))?;
+    Ok(())
+}
 
-    let builder = Builder::new(port_name, baudrate);
-    let request = Power::request(slave, &PowerMode::GetOnOff);
-    let response = builder.call(&request)?;
-    response.try_into()
+/// 关闭电源输出
+pub fn power_off(config: &PowerConfig) -> Result<()> {
+    set_power(config, &PowerMode::SetOutput(false))?;
+    Ok(())
 }
 
-/// 获取电源电压
-pub fn get_power_voltage(config: &RelayConfig, ab: AB) -> Result<PowerData> {
-    let port_name = config.serial_port.port.clone();
-    let baudrate = config.serial_port.baudrate.into();
-    let slave = config.slave;
-
-    let builder = Builder::new(port_name, baudrate);
-    let request = Power::request(slave, &PowerMode::GetVoltage);
-    let response = builder.call(&request)?;
-    response.try_into()
-}
-
-/// 设定电源
-pub fn set_power(config: &RelayConfig, ab: AB, mode: &PowerMode) -> Result<PowerData> {
-    let port_name = config.serial_port.port.clone();
-    let baudrate = config.serial_port.baudrate.into();
-    let slave = config.slave;
-
-    let builder = Builder::new(port_name, baudrate);
-    let request = Power::request(slave, mode);
-    let response = builder.call(&request)?;
-    response.try_into()
+/// 设定电源，浮点数按照配置的字序转换
+pub fn set_power(config: &PowerConfig, mode: &PowerMode) -> Result<PowerReply> {
+    let command = PowerCommand::new(*mode, config.word_order).with_features(config.features);
+    execute::<Power>(&config.serial_port, config.slave, &command)
 }
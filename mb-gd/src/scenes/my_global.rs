@@ -15,7 +15,7 @@ use mb_data::{
     utils::{get_time_offset, time_now},
 };
 
-use crate::{data::AB, utils::init_logging};
+use crate::{data::AB, mb_sync::set_capture, utils::init_logging};
 
 /// 单例：用于全局数据存储
 #[derive(GodotClass)]
@@ -99,6 +99,7 @@ impl MyGlobal {
                 conf.enable_a_panel = true;
             }
 
+            set_capture(conf.capture);
             self.config = Some(conf);
         }
 
@@ -115,6 +116,10 @@ impl MyGlobal {
             }
         };
 
+        // 端口配置可能变动，释放已打开的串口
+        mb::connection::close_all();
+        set_capture(config.capture);
+
         self.config = Some(config);
         self.base_mut().emit_signal("config_updated", &[]);
     }
@@ -8,7 +8,10 @@ use godot::{
     obj::WithBaseField,
     prelude::*,
 };
-use mb::utils::{hms_from_duration, hms_from_duration_string, time_from_hms};
+use mb::{
+    units::{Amps, Volts},
+    utils::{hms_from_duration, hms_from_duration_string, time_from_hms},
+};
 use strum::{AsRefStr, VariantArray};
 
 use mb_data::{
@@ -71,7 +74,7 @@ impl IPanelContainer for ProgramsView {
         }
 
         self.task.task_loop = 1;
-        self.task.power.voltage = 220;
+        self.task.power.voltage = Volts::new(220.0);
 
         let mut task_title_node = self.get_task_name_node();
         task_title_node.connect("text_changed", &self.base().callable("on_task_name"));
@@ -252,7 +255,7 @@ impl ProgramsView {
             .unwrap_or_default()
             .clamp(u32::MIN, u32::MAX);
 
-        self.task.voltage_verify.voltage_top = dur as f32;
+        self.task.voltage_verify.voltage_top = Volts::new(dur as f32);
 
         let len = text.len();
         number.set_text(&text);
@@ -269,7 +272,7 @@ impl ProgramsView {
             .unwrap_or_default()
             .clamp(u32::MIN, u32::MAX);
 
-        self.task.voltage_verify.voltage_down = dur as f32;
+        self.task.voltage_verify.voltage_down = Volts::new(dur as f32);
 
         let len = text.len();
         number.set_text(&text);
@@ -286,7 +289,7 @@ impl ProgramsView {
             .unwrap_or_default()
             .clamp(u32::MIN, u32::MAX);
 
-        self.task.voltage_verify.current_top = dur as f32;
+        self.task.voltage_verify.current_top = Amps::new(dur as f32);
 
         let len = text.len();
         number.set_text(&text);
@@ -303,7 +306,7 @@ impl ProgramsView {
             .unwrap_or_default()
             .clamp(u32::MIN, u32::MAX);
 
-        self.task.voltage_verify.current_down = dur as f32;
+        self.task.voltage_verify.current_down = Amps::new(dur as f32);
 
         let len = text.len();
         number.set_text(&text);
@@ -327,7 +330,7 @@ impl ProgramsView {
             .unwrap_or_default()
             .clamp(u32::MIN, u32::MAX);
 
-        self.task.power.voltage = dur;
+        self.task.power.voltage = Volts::new(dur as f32);
 
         let len = text.len();
         number.set_text(&text);
@@ -345,7 +348,7 @@ impl ProgramsView {
             .unwrap_or_default()
             .clamp(u32::MIN, u32::MAX);
 
-        self.task.power.current = dur;
+        self.task.power.current = Amps::new(dur as f32);
 
         let len = text.len();
         number.set_text(&text);
@@ -406,7 +409,7 @@ impl ProgramsView {
 
         if index == 0 {
             self.item.power_on = false;
-            self.item.voltage = 0;
+            self.item.voltage = Volts::ZERO;
         } else {
             self.item.power_on = true;
             self.item.voltage = self.task.power.voltage;
@@ -622,21 +625,21 @@ impl ProgramsView {
         self.get_task_name_node().set_text(&self.task.title.clone());
 
         self.get_voltage_top_node()
-            .set_text(&self.task.voltage_verify.voltage_top.to_string());
+            .set_text(&self.task.voltage_verify.voltage_top.value().to_string());
         self.get_voltage_down_node()
-            .set_text(&self.task.voltage_verify.voltage_down.to_string());
+            .set_text(&self.task.voltage_verify.voltage_down.value().to_string());
         self.get_current_top_node()
-            .set_text(&self.task.voltage_verify.current_top.to_string());
+            .set_text(&self.task.voltage_verify.current_top.value().to_string());
         self.get_current_down_node()
-            .set_text(&self.task.voltage_verify.current_down.to_string());
+            .set_text(&self.task.voltage_verify.current_down.value().to_string());
 
         self.get_power_type_node()
             .select(self.task.power.mode as i32);
 
         self.get_power_voltage_node()
-            .set_text(&self.task.power.voltage.to_string());
+            .set_text(&self.task.power.voltage.value().to_string());
         self.get_power_current_node()
-            .set_text(&self.task.power.current.to_string());
+            .set_text(&self.task.power.current.value().to_string());
 
         self.get_task_loop_node()
             .set_text(&self.task.task_loop.to_string());
@@ -652,7 +655,7 @@ impl ProgramsView {
         item_power_voltage_node.clear();
         let voltage = self.task.power.voltage;
         item_power_voltage_node.add_item("OFF");
-        item_power_voltage_node.add_item(&format!("{voltage}V"));
+        item_power_voltage_node.add_item(&voltage.to_string());
     }
 
     // 计算时间
@@ -740,7 +743,7 @@ impl ProgramsView {
             .map(|(index, task)| {
                 [
                     index.to_string(),
-                    task.voltage.to_string(),
+                    task.voltage.value().to_string(),
                     match task.power_on {
                         true => "老化中",
                         false => "断电",
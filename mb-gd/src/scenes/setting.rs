@@ -1,3 +1,5 @@
+use std::{collections::BTreeMap, str::FromStr};
+
 use godot::{
     classes::{
         AcceptDialog, Button, CheckBox, FileDialog, IPanelContainer, Label, LineEdit, OptionButton,
@@ -7,17 +9,26 @@ use godot::{
     },
     prelude::*,
 };
-use mb::protocol::get_ports;
+use mb::{
+    policy::Policy,
+    protocol::{Framing, get_ports},
+    register_map::WordOrder,
+    units::{Amps, Volts},
+};
 use strum::AsRefStr;
 
 use crate::{
     define_get_nodes,
+    mb_sync::{get_device_info, get_voltage_data},
     scenes::my_global::{get_global_config, set_global_config},
     utils::string_number_only,
 };
 use mb_data::{
+    calibration::CalibrationSession,
     config::{Baudrate, Config, DefectiveRule},
-    dirs::{data_dir, log_file},
+    db::{calibration::TableCalibration, device::TableDevice, get_db},
+    device::DeviceRecord,
+    dirs::{capture_file, data_dir, log_file},
 };
 
 use super::my_global::MyGlobal;
@@ -26,6 +37,8 @@ use super::my_global::MyGlobal;
 #[class(init,base=PanelContainer)]
 pub struct SettingView {
     config: Config,
+    /// 按端口的校准采集
+    calibration: BTreeMap<String, CalibrationSession>,
     file_dialog: Gd<PackedScene>,
     base: Base<PanelContainer>,
 }
@@ -79,6 +92,11 @@ impl SettingView {
         self.ab_init();
     }
 
+    #[func]
+    fn on_capture(&mut self, toggle_on: bool) {
+        self.config.capture = toggle_on;
+    }
+
     #[func]
     fn on_voltage_a_port_item_selected(&mut self, index: u32) {
         let ports = get_ports();
@@ -101,6 +119,11 @@ impl SettingView {
         self.config.voltage_a.serial_port.baudrate = sel;
     }
 
+    #[func]
+    fn on_voltage_a_framing_item_selected(&mut self, index: u32) {
+        select_framing(&mut self.config.voltage_a.serial_port.framing, index);
+    }
+
     #[func]
     fn on_voltage_b_port_item_selected(&mut self, index: u32) {
         let ports = get_ports();
@@ -123,6 +146,11 @@ impl SettingView {
         self.config.voltage_b.serial_port.baudrate = sel;
     }
 
+    #[func]
+    fn on_voltage_b_framing_item_selected(&mut self, index: u32) {
+        select_framing(&mut self.config.voltage_b.serial_port.framing, index);
+    }
+
     #[func]
     fn on_temp_port_item_selected(&mut self, index: u32) {
         let ports = get_ports();
@@ -145,6 +173,11 @@ impl SettingView {
         self.config.temperature.serial_port.baudrate = sel;
     }
 
+    #[func]
+    fn on_temp_framing_item_selected(&mut self, index: u32) {
+        select_framing(&mut self.config.temperature.serial_port.framing, index);
+    }
+
     #[func]
     fn on_relay_port_item_selected(&mut self, index: u32) {
         let ports = get_ports();
@@ -167,6 +200,11 @@ impl SettingView {
         self.config.relay.serial_port.baudrate = sel;
     }
 
+    #[func]
+    fn on_relay_framing_item_selected(&mut self, index: u32) {
+        select_framing(&mut self.config.relay.serial_port.framing, index);
+    }
+
     #[func]
     fn on_power_a_port_item_selected(&mut self, index: u32) {
         let ports = get_ports();
@@ -189,6 +227,21 @@ impl SettingView {
         self.config.power_a.serial_port.baudrate = sel;
     }
 
+    #[func]
+    fn on_power_a_framing_item_selected(&mut self, index: u32) {
+        select_framing(&mut self.config.power_a.serial_port.framing, index);
+    }
+
+    #[func]
+    fn on_power_a_word_order_item_selected(&mut self, index: u32) {
+        let sel = match WordOrder::ALL.get(index as usize) {
+            Some(&w) => w,
+            None => return,
+        };
+
+        self.config.power_a.word_order = sel;
+    }
+
     #[func]
     fn on_power_b_port_item_selected(&mut self, index: u32) {
         let ports = get_ports();
@@ -211,6 +264,21 @@ impl SettingView {
         self.config.power_b.serial_port.baudrate = sel;
     }
 
+    #[func]
+    fn on_power_b_framing_item_selected(&mut self, index: u32) {
+        select_framing(&mut self.config.power_b.serial_port.framing, index);
+    }
+
+    #[func]
+    fn on_power_b_word_order_item_selected(&mut self, index: u32) {
+        let sel = match WordOrder::ALL.get(index as usize) {
+            Some(&w) => w,
+            None => return,
+        };
+
+        self.config.power_b.word_order = sel;
+    }
+
     #[func]
     fn on_number_a_start(&mut self, text: String) {
         let mut number = self.get_voltage_a_start_num_node();
@@ -418,6 +486,174 @@ impl SettingView {
         self.config.history.export_dir = file_name.into();
     }
 
+    #[func]
+    fn on_voltage_a_timeout(&mut self, text: String) {
+        let number = self.get_voltage_a_timeout_node();
+        let policy = &mut self.config.voltage_a.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.timeout);
+    }
+
+    #[func]
+    fn on_voltage_a_retries(&mut self, text: String) {
+        let number = self.get_voltage_a_retries_node();
+        let policy = &mut self.config.voltage_a.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.retries);
+    }
+
+    #[func]
+    fn on_voltage_a_backoff(&mut self, text: String) {
+        let number = self.get_voltage_a_backoff_node();
+        let policy = &mut self.config.voltage_a.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.backoff);
+    }
+
+    #[func]
+    fn on_voltage_a_delay(&mut self, text: String) {
+        let number = self.get_voltage_a_delay_node();
+        let policy = &mut self.config.voltage_a.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.delay);
+    }
+
+    #[func]
+    fn on_voltage_b_timeout(&mut self, text: String) {
+        let number = self.get_voltage_b_timeout_node();
+        let policy = &mut self.config.voltage_b.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.timeout);
+    }
+
+    #[func]
+    fn on_voltage_b_retries(&mut self, text: String) {
+        let number = self.get_voltage_b_retries_node();
+        let policy = &mut self.config.voltage_b.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.retries);
+    }
+
+    #[func]
+    fn on_voltage_b_backoff(&mut self, text: String) {
+        let number = self.get_voltage_b_backoff_node();
+        let policy = &mut self.config.voltage_b.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.backoff);
+    }
+
+    #[func]
+    fn on_voltage_b_delay(&mut self, text: String) {
+        let number = self.get_voltage_b_delay_node();
+        let policy = &mut self.config.voltage_b.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.delay);
+    }
+
+    #[func]
+    fn on_temp_timeout(&mut self, text: String) {
+        let number = self.get_temp_timeout_node();
+        let policy = &mut self.config.temperature.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.timeout);
+    }
+
+    #[func]
+    fn on_temp_retries(&mut self, text: String) {
+        let number = self.get_temp_retries_node();
+        let policy = &mut self.config.temperature.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.retries);
+    }
+
+    #[func]
+    fn on_temp_backoff(&mut self, text: String) {
+        let number = self.get_temp_backoff_node();
+        let policy = &mut self.config.temperature.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.backoff);
+    }
+
+    #[func]
+    fn on_temp_delay(&mut self, text: String) {
+        let number = self.get_temp_delay_node();
+        let policy = &mut self.config.temperature.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.delay);
+    }
+
+    #[func]
+    fn on_relay_timeout(&mut self, text: String) {
+        let number = self.get_relay_timeout_node();
+        let policy = &mut self.config.relay.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.timeout);
+    }
+
+    #[func]
+    fn on_relay_retries(&mut self, text: String) {
+        let number = self.get_relay_retries_node();
+        let policy = &mut self.config.relay.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.retries);
+    }
+
+    #[func]
+    fn on_relay_backoff(&mut self, text: String) {
+        let number = self.get_relay_backoff_node();
+        let policy = &mut self.config.relay.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.backoff);
+    }
+
+    #[func]
+    fn on_relay_delay(&mut self, text: String) {
+        let number = self.get_relay_delay_node();
+        let policy = &mut self.config.relay.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.delay);
+    }
+
+    #[func]
+    fn on_power_a_timeout(&mut self, text: String) {
+        let number = self.get_power_a_timeout_node();
+        let policy = &mut self.config.power_a.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.timeout);
+    }
+
+    #[func]
+    fn on_power_a_retries(&mut self, text: String) {
+        let number = self.get_power_a_retries_node();
+        let policy = &mut self.config.power_a.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.retries);
+    }
+
+    #[func]
+    fn on_power_a_backoff(&mut self, text: String) {
+        let number = self.get_power_a_backoff_node();
+        let policy = &mut self.config.power_a.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.backoff);
+    }
+
+    #[func]
+    fn on_power_a_delay(&mut self, text: String) {
+        let number = self.get_power_a_delay_node();
+        let policy = &mut self.config.power_a.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.delay);
+    }
+
+    #[func]
+    fn on_power_b_timeout(&mut self, text: String) {
+        let number = self.get_power_b_timeout_node();
+        let policy = &mut self.config.power_b.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.timeout);
+    }
+
+    #[func]
+    fn on_power_b_retries(&mut self, text: String) {
+        let number = self.get_power_b_retries_node();
+        let policy = &mut self.config.power_b.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.retries);
+    }
+
+    #[func]
+    fn on_power_b_backoff(&mut self, text: String) {
+        let number = self.get_power_b_backoff_node();
+        let policy = &mut self.config.power_b.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.backoff);
+    }
+
+    #[func]
+    fn on_power_b_delay(&mut self, text: String) {
+        let number = self.get_power_b_delay_node();
+        let policy = &mut self.config.power_b.serial_port.policy;
+        edit_policy(number, text, policy, |p| &mut p.delay);
+    }
+
     #[func]
     fn on_submit(&mut self) {
         self.alert(
@@ -427,6 +663,123 @@ impl SettingView {
         );
         set_global_config(self.config.clone());
     }
+
+    /// 读取已启用面板上每个电压模块的设备标识并保存
+    #[func]
+    fn on_device_probe(&mut self) {
+        let mut voltages = Vec::new();
+        if self.config.enable_a_panel {
+            voltages.push(&self.config.voltage_a);
+        }
+        if self.config.enable_b_panel {
+            voltages.push(&self.config.voltage_b);
+        }
+
+        let mut info = Vec::new();
+        for voltage in voltages {
+            let port = &voltage.serial_port.port;
+            for slave in voltage.slave_start..=voltage.slave_end {
+                let record = match get_device_info(voltage, slave) {
+                    Ok(data) => DeviceRecord::new(port, &data),
+                    Err(e) => {
+                        log::warn!("{port} 从站 {slave} 设备信息读取失败: {e}");
+                        info.push(format!("{port} #{slave}: {e}"));
+                        continue;
+                    }
+                };
+
+                info.push(format!(
+                    "{port} #{slave}: {} {} {}",
+                    record.vendor, record.model, record.revision
+                ));
+                let db = get_db().lock().unwrap();
+                if let Err(e) = TableDevice::set(&db, &record) {
+                    log::error!("保存设备信息失败 {e}");
+                }
+            }
+        }
+
+        if info.is_empty() {
+            info.push("没有启用的电压模块".to_owned());
+        }
+        self.alert("设备信息".to_owned(), "确认".to_owned(), info.join("\n"));
+    }
+
+    /// 按照参考值采集一次读数，可以换不同的参考值多次采集
+    #[func]
+    fn on_calibration_capture(&mut self) {
+        let reference = |text: GString| text.to_string().trim().parse::<f32>().ok();
+        let voltage = reference(self.get_calibration_voltage_node().get_text()).map(Volts::new);
+        let current = reference(self.get_calibration_current_node().get_text()).map(Amps::new);
+        if voltage.is_none() && current.is_none() {
+            self.alert(
+                "校准".to_owned(),
+                "确认".to_owned(),
+                "请输入参考电压或电流".to_owned(),
+            );
+            return;
+        }
+
+        let mut voltages = Vec::new();
+        if self.config.enable_a_panel {
+            voltages.push(&self.config.voltage_a);
+        }
+        if self.config.enable_b_panel {
+            voltages.push(&self.config.voltage_b);
+        }
+
+        let mut info = Vec::new();
+        for config in voltages {
+            let port = &config.serial_port.port;
+            let session = self
+                .calibration
+                .entry(port.clone())
+                .or_insert_with(|| CalibrationSession::new(port));
+            for slave in config.slave_start..=config.slave_end {
+                match get_voltage_data(config, slave) {
+                    Ok(data) => session.capture(&data, voltage, current),
+                    Err(e) => {
+                        log::warn!("{port} 从站 {slave} 校准采集失败: {e}");
+                        info.push(format!("{port} #{slave}: {e}"));
+                    }
+                }
+            }
+            info.push(format!("{port}: 已采集 {} 次", session.count()));
+        }
+
+        if info.is_empty() {
+            info.push("没有启用的电压模块".to_owned());
+        }
+        self.alert("校准".to_owned(), "确认".to_owned(), info.join("\n"));
+    }
+
+    /// 按通道拟合增益和偏移并保存
+    #[func]
+    fn on_calibration_save(&mut self) {
+        let sessions = std::mem::take(&mut self.calibration);
+
+        let mut info = Vec::new();
+        for record in sessions.values().flat_map(CalibrationSession::finish) {
+            let db = get_db().lock().unwrap();
+            match TableCalibration::set(&db, &record) {
+                Ok(_) => info.push(format!(
+                    "{} #{}: {} 个通道",
+                    record.port,
+                    record.slave,
+                    record.channels.len()
+                )),
+                Err(e) => {
+                    log::error!("保存校准系数失败 {e}");
+                    info.push(format!("{} #{}: {e}", record.port, record.slave));
+                }
+            }
+        }
+
+        if info.is_empty() {
+            info.push("没有校准采集数据".to_owned());
+        }
+        self.alert("校准".to_owned(), "确认".to_owned(), info.join("\n"));
+    }
 }
 
 impl SettingView {
@@ -595,6 +948,178 @@ impl SettingView {
             &self.base().callable("on_power_b_baudrate_item_selected"),
         );
 
+        // --- framing ---
+
+        let mut voltage_a_framing_btn = self.get_voltage_a_framing_node();
+        let mut voltage_b_framing_btn = self.get_voltage_b_framing_node();
+        let mut temp_framing_btn = self.get_temp_framing_node();
+        let mut relay_framing_btn = self.get_relay_framing_node();
+        let mut power_a_framing_btn = self.get_power_a_framing_node();
+        let mut power_b_framing_btn = self.get_power_b_framing_node();
+
+        for (index, &item) in Framing::ALL.iter().enumerate() {
+            voltage_a_framing_btn.add_item(&item.to_string());
+            voltage_b_framing_btn.add_item(&item.to_string());
+            temp_framing_btn.add_item(&item.to_string());
+            relay_framing_btn.add_item(&item.to_string());
+            power_a_framing_btn.add_item(&item.to_string());
+            power_b_framing_btn.add_item(&item.to_string());
+
+            let index = index as i32;
+
+            if item == self.config.voltage_a.serial_port.framing {
+                voltage_a_framing_btn.select(index);
+            }
+            if item == self.config.voltage_b.serial_port.framing {
+                voltage_b_framing_btn.select(index);
+            }
+            if item == self.config.temperature.serial_port.framing {
+                temp_framing_btn.select(index);
+            }
+            if item == self.config.relay.serial_port.framing {
+                relay_framing_btn.select(index);
+            }
+            if item == self.config.power_a.serial_port.framing {
+                power_a_framing_btn.select(index);
+            }
+            if item == self.config.power_b.serial_port.framing {
+                power_b_framing_btn.select(index);
+            }
+        }
+
+        voltage_a_framing_btn.connect(
+            "item_selected",
+            &self.base().callable("on_voltage_a_framing_item_selected"),
+        );
+        voltage_b_framing_btn.connect(
+            "item_selected",
+            &self.base().callable("on_voltage_b_framing_item_selected"),
+        );
+        temp_framing_btn.connect(
+            "item_selected",
+            &self.base().callable("on_temp_framing_item_selected"),
+        );
+        relay_framing_btn.connect(
+            "item_selected",
+            &self.base().callable("on_relay_framing_item_selected"),
+        );
+        power_a_framing_btn.connect(
+            "item_selected",
+            &self.base().callable("on_power_a_framing_item_selected"),
+        );
+        power_b_framing_btn.connect(
+            "item_selected",
+            &self.base().callable("on_power_b_framing_item_selected"),
+        );
+
+        // --- word order ---
+
+        let mut power_a_word_order_btn = self.get_power_a_word_order_node();
+        let mut power_b_word_order_btn = self.get_power_b_word_order_node();
+
+        for (index, &item) in WordOrder::ALL.iter().enumerate() {
+            power_a_word_order_btn.add_item(&item.to_string());
+            power_b_word_order_btn.add_item(&item.to_string());
+
+            let index = index as i32;
+
+            if item == self.config.power_a.word_order {
+                power_a_word_order_btn.select(index);
+            }
+            if item == self.config.power_b.word_order {
+                power_b_word_order_btn.select(index);
+            }
+        }
+
+        power_a_word_order_btn.connect(
+            "item_selected",
+            &self.base().callable("on_power_a_word_order_item_selected"),
+        );
+        power_b_word_order_btn.connect(
+            "item_selected",
+            &self.base().callable("on_power_b_word_order_item_selected"),
+        );
+
+        // --- policy ---
+
+        let mut voltage_a_timeout = self.get_voltage_a_timeout_node();
+        voltage_a_timeout.set_text(&self.config.voltage_a.serial_port.policy.timeout.to_string());
+        voltage_a_timeout.connect("text_changed", &self.base().callable("on_voltage_a_timeout"));
+        let mut voltage_a_retries = self.get_voltage_a_retries_node();
+        voltage_a_retries.set_text(&self.config.voltage_a.serial_port.policy.retries.to_string());
+        voltage_a_retries.connect("text_changed", &self.base().callable("on_voltage_a_retries"));
+        let mut voltage_a_backoff = self.get_voltage_a_backoff_node();
+        voltage_a_backoff.set_text(&self.config.voltage_a.serial_port.policy.backoff.to_string());
+        voltage_a_backoff.connect("text_changed", &self.base().callable("on_voltage_a_backoff"));
+        let mut voltage_a_delay = self.get_voltage_a_delay_node();
+        voltage_a_delay.set_text(&self.config.voltage_a.serial_port.policy.delay.to_string());
+        voltage_a_delay.connect("text_changed", &self.base().callable("on_voltage_a_delay"));
+
+        let mut voltage_b_timeout = self.get_voltage_b_timeout_node();
+        voltage_b_timeout.set_text(&self.config.voltage_b.serial_port.policy.timeout.to_string());
+        voltage_b_timeout.connect("text_changed", &self.base().callable("on_voltage_b_timeout"));
+        let mut voltage_b_retries = self.get_voltage_b_retries_node();
+        voltage_b_retries.set_text(&self.config.voltage_b.serial_port.policy.retries.to_string());
+        voltage_b_retries.connect("text_changed", &self.base().callable("on_voltage_b_retries"));
+        let mut voltage_b_backoff = self.get_voltage_b_backoff_node();
+        voltage_b_backoff.set_text(&self.config.voltage_b.serial_port.policy.backoff.to_string());
+        voltage_b_backoff.connect("text_changed", &self.base().callable("on_voltage_b_backoff"));
+        let mut voltage_b_delay = self.get_voltage_b_delay_node();
+        voltage_b_delay.set_text(&self.config.voltage_b.serial_port.policy.delay.to_string());
+        voltage_b_delay.connect("text_changed", &self.base().callable("on_voltage_b_delay"));
+
+        let mut temp_timeout = self.get_temp_timeout_node();
+        temp_timeout.set_text(&self.config.temperature.serial_port.policy.timeout.to_string());
+        temp_timeout.connect("text_changed", &self.base().callable("on_temp_timeout"));
+        let mut temp_retries = self.get_temp_retries_node();
+        temp_retries.set_text(&self.config.temperature.serial_port.policy.retries.to_string());
+        temp_retries.connect("text_changed", &self.base().callable("on_temp_retries"));
+        let mut temp_backoff = self.get_temp_backoff_node();
+        temp_backoff.set_text(&self.config.temperature.serial_port.policy.backoff.to_string());
+        temp_backoff.connect("text_changed", &self.base().callable("on_temp_backoff"));
+        let mut temp_delay = self.get_temp_delay_node();
+        temp_delay.set_text(&self.config.temperature.serial_port.policy.delay.to_string());
+        temp_delay.connect("text_changed", &self.base().callable("on_temp_delay"));
+
+        let mut relay_timeout = self.get_relay_timeout_node();
+        relay_timeout.set_text(&self.config.relay.serial_port.policy.timeout.to_string());
+        relay_timeout.connect("text_changed", &self.base().callable("on_relay_timeout"));
+        let mut relay_retries = self.get_relay_retries_node();
+        relay_retries.set_text(&self.config.relay.serial_port.policy.retries.to_string());
+        relay_retries.connect("text_changed", &self.base().callable("on_relay_retries"));
+        let mut relay_backoff = self.get_relay_backoff_node();
+        relay_backoff.set_text(&self.config.relay.serial_port.policy.backoff.to_string());
+        relay_backoff.connect("text_changed", &self.base().callable("on_relay_backoff"));
+        let mut relay_delay = self.get_relay_delay_node();
+        relay_delay.set_text(&self.config.relay.serial_port.policy.delay.to_string());
+        relay_delay.connect("text_changed", &self.base().callable("on_relay_delay"));
+
+        let mut power_a_timeout = self.get_power_a_timeout_node();
+        power_a_timeout.set_text(&self.config.power_a.serial_port.policy.timeout.to_string());
+        power_a_timeout.connect("text_changed", &self.base().callable("on_power_a_timeout"));
+        let mut power_a_retries = self.get_power_a_retries_node();
+        power_a_retries.set_text(&self.config.power_a.serial_port.policy.retries.to_string());
+        power_a_retries.connect("text_changed", &self.base().callable("on_power_a_retries"));
+        let mut power_a_backoff = self.get_power_a_backoff_node();
+        power_a_backoff.set_text(&self.config.power_a.serial_port.policy.backoff.to_string());
+        power_a_backoff.connect("text_changed", &self.base().callable("on_power_a_backoff"));
+        let mut power_a_delay = self.get_power_a_delay_node();
+        power_a_delay.set_text(&self.config.power_a.serial_port.policy.delay.to_string());
+        power_a_delay.connect("text_changed", &self.base().callable("on_power_a_delay"));
+
+        let mut power_b_timeout = self.get_power_b_timeout_node();
+        power_b_timeout.set_text(&self.config.power_b.serial_port.policy.timeout.to_string());
+        power_b_timeout.connect("text_changed", &self.base().callable("on_power_b_timeout"));
+        let mut power_b_retries = self.get_power_b_retries_node();
+        power_b_retries.set_text(&self.config.power_b.serial_port.policy.retries.to_string());
+        power_b_retries.connect("text_changed", &self.base().callable("on_power_b_retries"));
+        let mut power_b_backoff = self.get_power_b_backoff_node();
+        power_b_backoff.set_text(&self.config.power_b.serial_port.policy.backoff.to_string());
+        power_b_backoff.connect("text_changed", &self.base().callable("on_power_b_backoff"));
+        let mut power_b_delay = self.get_power_b_delay_node();
+        power_b_delay.set_text(&self.config.power_b.serial_port.policy.delay.to_string());
+        power_b_delay.connect("text_changed", &self.base().callable("on_power_b_delay"));
+
         // --- slave ---
 
         let mut number_a_start = self.get_voltage_a_start_num_node();
@@ -633,6 +1158,14 @@ impl SettingView {
 
         let mut submit_btn = self.get_submit_node();
         submit_btn.connect("pressed", &self.base().callable("on_submit"));
+
+        let mut device_probe_btn = self.get_device_probe_node();
+        device_probe_btn.connect("pressed", &self.base().callable("on_device_probe"));
+
+        let mut calibration_capture_btn = self.get_calibration_capture_node();
+        calibration_capture_btn.connect("pressed", &self.base().callable("on_calibration_capture"));
+        let mut calibration_save_btn = self.get_calibration_save_node();
+        calibration_save_btn.connect("pressed", &self.base().callable("on_calibration_save"));
     }
 
     fn defective_init(&mut self) {
@@ -711,6 +1244,12 @@ impl SettingView {
                 .set_text(&data_dir().to_string_lossy().to_string());
             self.get_path_log_node()
                 .set_text(&log_file().to_string_lossy().to_string());
+
+            let mut capture = self.get_capture_node();
+            capture.set_pressed(self.config.capture);
+            capture.connect("toggled", &self.base().callable("on_capture"));
+            self.get_path_capture_node()
+                .set_text(&capture_file().to_string_lossy().to_string());
         };
     }
 
@@ -730,6 +1269,15 @@ impl SettingView {
             UniqueName::VoltageABaudrate,
             OptionButton
         ),
+        (
+            get_voltage_a_framing_node,
+            UniqueName::VoltageAFraming,
+            OptionButton
+        ),
+        (get_voltage_a_timeout_node, UniqueName::VoltageATimeout, LineEdit),
+        (get_voltage_a_retries_node, UniqueName::VoltageARetries, LineEdit),
+        (get_voltage_a_backoff_node, UniqueName::VoltageABackoff, LineEdit),
+        (get_voltage_a_delay_node, UniqueName::VoltageADelay, LineEdit),
         (
             get_voltage_a_start_num_node,
             UniqueName::VoltageAStartNum,
@@ -750,6 +1298,15 @@ impl SettingView {
             UniqueName::VoltageBBaudrate,
             OptionButton
         ),
+        (
+            get_voltage_b_framing_node,
+            UniqueName::VoltageBFraming,
+            OptionButton
+        ),
+        (get_voltage_b_timeout_node, UniqueName::VoltageBTimeout, LineEdit),
+        (get_voltage_b_retries_node, UniqueName::VoltageBRetries, LineEdit),
+        (get_voltage_b_backoff_node, UniqueName::VoltageBBackoff, LineEdit),
+        (get_voltage_b_delay_node, UniqueName::VoltageBDelay, LineEdit),
         (
             get_voltage_b_start_num_node,
             UniqueName::VoltageBStartNum,
@@ -766,6 +1323,15 @@ impl SettingView {
             UniqueName::TempBaudrate,
             OptionButton
         ),
+        (
+            get_temp_framing_node,
+            UniqueName::TempFraming,
+            OptionButton
+        ),
+        (get_temp_timeout_node, UniqueName::TempTimeout, LineEdit),
+        (get_temp_retries_node, UniqueName::TempRetries, LineEdit),
+        (get_temp_backoff_node, UniqueName::TempBackoff, LineEdit),
+        (get_temp_delay_node, UniqueName::TempDelay, LineEdit),
         (get_temp_slave_node, UniqueName::TempSlave, LineEdit),
         (get_relay_port_node, UniqueName::RelayPort, OptionButton),
         (
@@ -773,6 +1339,15 @@ impl SettingView {
             UniqueName::RelayBaudrate,
             OptionButton
         ),
+        (
+            get_relay_framing_node,
+            UniqueName::RelayFraming,
+            OptionButton
+        ),
+        (get_relay_timeout_node, UniqueName::RelayTimeout, LineEdit),
+        (get_relay_retries_node, UniqueName::RelayRetries, LineEdit),
+        (get_relay_backoff_node, UniqueName::RelayBackoff, LineEdit),
+        (get_relay_delay_node, UniqueName::RelayDelay, LineEdit),
         (get_relay_slave_node, UniqueName::RelaySlave, LineEdit),
         (get_power_a_port_node, UniqueName::PowerAPort, OptionButton),
         (
@@ -780,6 +1355,20 @@ impl SettingView {
             UniqueName::PowerABaudrate,
             OptionButton
         ),
+        (
+            get_power_a_framing_node,
+            UniqueName::PowerAFraming,
+            OptionButton
+        ),
+        (
+            get_power_a_word_order_node,
+            UniqueName::PowerAWordOrder,
+            OptionButton
+        ),
+        (get_power_a_timeout_node, UniqueName::PowerATimeout, LineEdit),
+        (get_power_a_retries_node, UniqueName::PowerARetries, LineEdit),
+        (get_power_a_backoff_node, UniqueName::PowerABackoff, LineEdit),
+        (get_power_a_delay_node, UniqueName::PowerADelay, LineEdit),
         (get_power_a_slave_node, UniqueName::PowerASlave, LineEdit),
         (get_power_b_port_node, UniqueName::PowerBPort, OptionButton),
         (
@@ -787,6 +1376,20 @@ impl SettingView {
             UniqueName::PowerBBaudrate,
             OptionButton
         ),
+        (
+            get_power_b_framing_node,
+            UniqueName::PowerBFraming,
+            OptionButton
+        ),
+        (
+            get_power_b_word_order_node,
+            UniqueName::PowerBWordOrder,
+            OptionButton
+        ),
+        (get_power_b_timeout_node, UniqueName::PowerBTimeout, LineEdit),
+        (get_power_b_retries_node, UniqueName::PowerBRetries, LineEdit),
+        (get_power_b_backoff_node, UniqueName::PowerBBackoff, LineEdit),
+        (get_power_b_delay_node, UniqueName::PowerBDelay, LineEdit),
         (get_power_b_slave_node, UniqueName::PowerBSlave, LineEdit),
         (
             get_defective_rule_node,
@@ -814,12 +1417,35 @@ impl SettingView {
             UniqueName::HistoryExportDir,
             RichTextLabel
         ),
+        (get_device_probe_node, UniqueName::DeviceProbe, Button),
+        (
+            get_calibration_voltage_node,
+            UniqueName::CalibrationVoltage,
+            LineEdit
+        ),
+        (
+            get_calibration_current_node,
+            UniqueName::CalibrationCurrent,
+            LineEdit
+        ),
+        (
+            get_calibration_capture_node,
+            UniqueName::CalibrationCapture,
+            Button
+        ),
+        (
+            get_calibration_save_node,
+            UniqueName::CalibrationSave,
+            Button
+        ),
         (get_submit_node, UniqueName::Submit, Button),
         (get_alert_node, UniqueName::Alert, AcceptDialog),
         (get_alert_info_node, UniqueName::AlertInfo, Label),
         (get_debug_panel_node, UniqueName::DebugPanel, PanelContainer),
         (get_path_data_node, UniqueName::PathData, RichTextLabel),
         (get_path_log_node, UniqueName::PathLog, RichTextLabel),
+        (get_capture_node, UniqueName::Capture, CheckBox),
+        (get_path_capture_node, UniqueName::PathCapture, RichTextLabel),
     ];
 }
 
@@ -835,28 +1461,60 @@ enum UniqueName {
 
     VoltageAPort,
     VoltageABaudrate,
+    VoltageAFraming,
+    VoltageATimeout,
+    VoltageARetries,
+    VoltageABackoff,
+    VoltageADelay,
     VoltageAStartNum,
     VoltageAEndNum,
 
     VoltageBPort,
     VoltageBBaudrate,
+    VoltageBFraming,
+    VoltageBTimeout,
+    VoltageBRetries,
+    VoltageBBackoff,
+    VoltageBDelay,
     VoltageBStartNum,
     VoltageBEndNum,
 
     TempPort,
     TempBaudrate,
+    TempFraming,
+    TempTimeout,
+    TempRetries,
+    TempBackoff,
+    TempDelay,
     TempSlave,
 
     RelayPort,
     RelayBaudrate,
+    RelayFraming,
+    RelayTimeout,
+    RelayRetries,
+    RelayBackoff,
+    RelayDelay,
     RelaySlave,
 
     PowerAPort,
     PowerABaudrate,
+    PowerAFraming,
+    PowerAWordOrder,
+    PowerATimeout,
+    PowerARetries,
+    PowerABackoff,
+    PowerADelay,
     PowerASlave,
 
     PowerBPort,
     PowerBBaudrate,
+    PowerBFraming,
+    PowerBWordOrder,
+    PowerBTimeout,
+    PowerBRetries,
+    PowerBBackoff,
+    PowerBDelay,
     PowerBSlave,
 
     DefectiveRule,
@@ -867,6 +1525,11 @@ enum UniqueName {
     HistoryExportDirBtn,
     HistoryExportDir,
 
+    DeviceProbe,
+    CalibrationVoltage,
+    CalibrationCurrent,
+    CalibrationCapture,
+    CalibrationSave,
     Submit,
 
     Alert,
@@ -875,6 +1538,31 @@ enum UniqueName {
     DebugPanel,
     PathData,
     PathLog,
+    Capture,
+    PathCapture,
+}
+
+/// 只保留输入框中的数字并写回，解析后写入策略的一个字段，无法解析时为 0
+fn edit_policy<T>(
+    mut number: Gd<LineEdit>,
+    text: String,
+    policy: &mut Policy,
+    field: impl FnOnce(&mut Policy) -> &mut T,
+) where
+    T: FromStr + Default,
+{
+    let text = string_number_only(text);
+    *field(policy) = text.parse().unwrap_or_default();
+    let len = text.len();
+    number.set_text(&text);
+    number.set_caret_column(len as i32);
+}
+
+/// 按选项下标设置帧格式，下标无效时不修改
+fn select_framing(framing: &mut Framing, index: u32) {
+    if let Some(&f) = Framing::ALL.get(index as usize) {
+        *framing = f;
+    }
 }
 
 impl std::fmt::Display for UniqueName {
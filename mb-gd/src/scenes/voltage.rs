@@ -6,13 +6,15 @@ use godot::{
     obj::WithBaseField,
     prelude::*,
 };
-use mb::{Result, voltage::VoltageChannel};
+use mb::voltage::VoltageChannel;
 use mb::{
+    power::Protection,
     relay::RelayMode,
     utils::{current_timestamp, hms_from_duration_string},
-    voltage::{VOLTAGE_CHANNEL, VoltageData, VoltageState},
+    voltage::{VoltageData, VoltageState},
 };
 use mb_data::{
+    config::PowerConfig,
     db::{
         get_db,
         voltage::{
@@ -34,7 +36,10 @@ use crate::{
     colors::{ColorPlate, IntoColor},
     data::AB,
     define_get_nodes,
-    mb_sync::{get_relay, get_temperature, get_voltage_data, set_relay},
+    error::Result,
+    mb_sync::{
+        get_power_status, get_temperature, get_voltage_data, power_off, power_on, set_relay,
+    },
     scenes::my_global::get_global_config,
 };
 
@@ -254,15 +259,35 @@ impl VoltageView {
 
     #[func]
     fn on_power_toggle(&mut self) {
-        self.state = match self.state {
-            State::Run => State::Power,
-            State::Power => State::Run,
+        let task = match &self.task {
+            Some(task) => task.clone(),
+            None => return,
+        };
+        let config = self.power_config();
+
+        let result = match self.state {
+            State::Run => power_on(
+                &config,
+                task.power.mode.into(),
+                task.power.voltage,
+                task.power.current,
+            ),
+            State::Power => power_off(&config),
             _ => {
                 return;
             }
         };
 
-        // TODO 冲击电源kk开关
+        // 电源未确认时保持原状态
+        if let Err(e) = result {
+            log::error!("电源开关失败： {e}");
+            return;
+        }
+
+        self.state = match self.state {
+            State::Run => State::Power,
+            _ => State::Run,
+        };
 
         self.btn_state_update();
     }
@@ -320,23 +345,10 @@ impl VoltageView {
 
             self.task_state.power_on = item.power_on;
 
-            // 获取继电器
-            let relay = match get_relay(&config, self.ab) {
-                Ok(data) => data,
-                Err(e) => {
-                    log::error!("继电器冲击失败： {e}");
-                    return;
-                }
-            };
+            // 冲击开关，掩码写入只修改本区的位，不会覆盖另一区
+            let mode = RelayMode::Switch(pos, item.power_on);
 
-            let mode = if item.power_on {
-                // 冲击开关
-                RelayMode::ON(relay.value, pos)
-            } else {
-                RelayMode::OFF(relay.value, pos)
-            };
-
-            match set_relay(&config, self.ab, &mode) {
+            match set_relay(&config, &mode) {
                 Ok(data) => data,
                 Err(e) => {
                     log::error!("继电器冲击失败： {e}");
@@ -379,7 +391,7 @@ impl VoltageView {
         self.count_time = task.count_time;
         self.count_down = task.count_time;
         power_state_node.set_text(&format!(
-            "{} {}V",
+            "{} {}",
             task.power.mode.as_ref(),
             task.power.voltage
         ));
@@ -479,12 +491,38 @@ impl VoltageView {
         }
     }
 
+    /// 根据 AB 区 获取电源参数
+    fn power_config(&self) -> PowerConfig {
+        let config = get_global_config();
+        match self.ab {
+            AB::Apanel => config.power_a,
+            AB::Bpanel => config.power_b,
+        }
+    }
+
     /// 电源开启后监控
     fn power_state_update(&mut self) {
-        // if self.task.is_none() {
-        //     return;
-        // }
-        // TODO 检查电源
+        if self.task.is_none() || self.state != State::Ageing {
+            return;
+        }
+
+        let status = match get_power_status(&self.power_config()) {
+            Ok(status) => status,
+            Err(e) => {
+                log::error!("读取电源状态失败： {e}");
+                return;
+            }
+        };
+
+        let mut text = format!("{:.1} {:.2}", status.voltage, status.current);
+        if let Some(mode) = status.mode {
+            text = format!("{mode} {text}");
+        }
+        if let Some(protection) = status.protection.filter(Protection::any) {
+            log::error!("电源保护： {protection:?}");
+            text.push_str(" 保护");
+        }
+        self.get_power_state_node().set_text(&text);
     }
 
     fn btn_state_update(&mut self) {
@@ -545,7 +583,7 @@ impl VoltageView {
             AB::Bpanel => config.voltage_b.clone(),
         };
 
-        let count = (voltage.slave_start..=voltage.slave_end).count() * VOLTAGE_CHANNEL;
+        let count = (voltage.slave_start..=voltage.slave_end).count() * voltage.layout.channels;
 
         // 计算宽度
         self.count_num = count as u64;
@@ -557,6 +595,9 @@ impl VoltageView {
             container.add_child(&channel_scene);
             channel_scene.set_name(&name);
             channel_scene.bind_mut().set_index(index);
+            channel_scene
+                .bind_mut()
+                .set_units(voltage.layout.voltage_unit, voltage.layout.current_unit);
             channel_scene.bind_mut().update_ui();
         });
 
@@ -617,32 +658,50 @@ impl VoltageView {
         label_ab_name.set_text(&self.ab.title());
 
         let temperature = match get_temperature(&config.temperature, self.ab) {
-            Ok(t) => t.value,
+            Ok(t) => Some(t.value),
+            // 超时等待下次读取
+            Err(e) if e.is_timeout() => {
+                log::warn!("温度获取超时: {}", e);
+                None
+            }
             Err(e) => {
                 self.on_ageing_toggle();
                 log::error!("温度获取失败: {}", e);
-                0.
+                None
             }
         };
 
+        // 单个从站无响应时标记为未连接，端口断开时停止老化
+        let channels = voltage.layout.channels;
+        let mut port_gone = false;
         let data: Vec<VoltageData> = (voltage.slave_start..=voltage.slave_end)
             .enumerate()
-            .map(|(index, slave)| match get_voltage_data(&voltage, slave) {
-                Ok(d) => {
-                    let mut data = d;
-                    data.update_channel_state(&voltage.verify);
-                    data.update_channel_index(index);
-                    data
-                }
-                Err(e) => {
-                    self.on_ageing_toggle();
-                    log::error!("电压电流获取失败: {}", e);
-                    VoltageData::new(Duration::from_secs(0), 0, Vec::new())
-                }
+            .map(|(index, slave)| {
+                let mut data = match get_voltage_data(&voltage, slave) {
+                    Ok(mut data) => {
+                        data.update_channel_state(&voltage.verify);
+                        data
+                    }
+                    Err(e) => {
+                        port_gone |= e.is_port_gone();
+                        log::warn!("电压电流获取失败, 从站 {slave} 离线: {}", e);
+                        VoltageData::offline(slave, channels)
+                    }
+                };
+                data.update_channel_index(index, channels);
+                data
             })
             .collect();
 
-        if !data.is_empty() {
+        if port_gone {
+            log::error!("电压电流端口断开: {}", voltage.serial_port.port);
+            self.on_ageing_toggle();
+        }
+
+        // 离线的从站不计入历史数据
+        let data_online: Vec<VoltageData> =
+            data.iter().filter(|d| !d.is_offline()).cloned().collect();
+        if !data_online.is_empty() {
             let data_group = VoltageDataGroup {
                 time: current_timestamp(),
                 ab: self.ab.into(),
@@ -651,7 +710,7 @@ impl VoltageView {
                 start_at: self.start_at,
                 task_age_time: task.count_time,
                 temperature,
-                data: data.clone(),
+                data: data_online,
             };
             {
                 let db = get_db().lock().unwrap();
@@ -665,7 +724,7 @@ impl VoltageView {
 
         for (j, data) in data.iter().enumerate() {
             for (i, data) in data.data.iter().enumerate() {
-                let index = i + j * VOLTAGE_CHANNEL;
+                let index = i + j * channels;
                 let name = format!("i{}", index);
                 let mut channel = content.get_node_as::<VoltageChannelView>(&name);
 
@@ -705,7 +764,7 @@ impl VoltageView {
             .rev()
             .take(60)
             .enumerate()
-            .map(|(index, data)| Vector2::new(index as f32, data.voltage()))
+            .map(|(index, data)| Vector2::new(index as f32, data.voltage().value()))
             .collect();
 
         let mut chart = self.get_chart_node();
@@ -886,17 +945,21 @@ impl VoltageView {
         let worksheet = workbook.add_worksheet();
 
         worksheet.write(0, 0, "Channel")?;
-        worksheet.write(0, 1, "电压")?;
-        worksheet.write(0, 2, "电流")?;
+        worksheet.write(0, 1, "电压 V")?;
+        worksheet.write(0, 2, "电流 A")?;
         worksheet.write(0, 3, "时间")?;
+        worksheet.write(0, 4, "原始电压 V")?;
+        worksheet.write(0, 5, "原始电流 A")?;
 
         for item in list.iter() {
             let channel = item.ch;
             let row = item.index as u32 + 1;
             worksheet.write(row, 0, channel.index as u64)?;
-            worksheet.write(row, 1, channel.voltage)?;
-            worksheet.write(row, 2, channel.current)?;
+            worksheet.write(row, 1, channel.voltage.value())?;
+            worksheet.write(row, 2, channel.current.value())?;
             worksheet.write(row, 3, time_human(time_dur_odt(item.time)))?;
+            worksheet.write(row, 4, channel.raw_voltage.value())?;
+            worksheet.write(row, 5, channel.raw_current.value())?;
         }
 
         workbook.save(doc_path)?;
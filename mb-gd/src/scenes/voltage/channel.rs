@@ -3,7 +3,7 @@ use godot::{
     obj::WithBaseField,
     prelude::*,
 };
-use mb::voltage::VoltageChannel;
+use mb::voltage::{CurrentUnit, VoltageChannel, VoltageUnit};
 use strum::AsRefStr;
 
 use crate::{colors::ColorPlate, define_get_nodes};
@@ -16,6 +16,8 @@ pub struct VoltageChannelView {
     // slave index
     index: usize,
     data: VoltageChannel,
+    // 电压、电流单位
+    units: (VoltageUnit, CurrentUnit),
     base: Base<PanelContainer>,
 }
 
@@ -25,6 +27,7 @@ impl IPanelContainer for VoltageChannelView {
         Self {
             index: 0,
             data: VoltageChannel::default(),
+            units: Default::default(),
             color: ColorPlate::White.into(),
             base,
         }
@@ -55,6 +58,10 @@ impl VoltageChannelView {
         self.index = index;
     }
 
+    pub fn set_units(&mut self, voltage: VoltageUnit, current: CurrentUnit) {
+        self.units = (voltage, current);
+    }
+
     pub fn update_ui(&mut self) {
         let mut index_label = self.get_index_node();
         let mut voltage_label = self.get_voltage_node();
@@ -68,8 +75,11 @@ impl VoltageChannelView {
         } + 1;
 
         index_label.set_text(&format!("{:2}", index));
-        voltage_label.set_text(&format!("{:2.2}V", self.data.voltage));
-        current_label.set_text(&format!("{:2.2}A", self.data.current));
+        let (voltage_unit, current_unit) = self.units;
+        let voltage = voltage_unit.value(self.data.voltage);
+        let current = current_unit.value(self.data.current);
+        voltage_label.set_text(&format!("{voltage:2.2}{voltage_unit}"));
+        current_label.set_text(&format!("{current:2.2}{current_unit}"));
 
         state.set_modulate(self.color);
     }
@@ -1,9 +1,10 @@
-use mb::Result;
 use mb_data::{
     dirs,
     utils::{time_human, time_now},
 };
 
+use crate::error::Result;
+
 /// 日志
 pub fn init_logging(verbosity: u64) -> Result<()> {
     let mut base_config = fern::Dispatch::new();
@@ -0,0 +1,59 @@
+use mb::protocol::{FunRequest, FunResponse, Function, FunctionCode};
+
+use crate::Mock;
+
+/// 模拟设备的诊断与设备标识，所有从站通用
+pub struct DeviceMock {
+    req: Function,
+}
+
+impl DeviceMock {
+    /// 诊断、通信事件计数器、设备标识请求
+    pub fn accepts(buffer: &[u8]) -> bool {
+        matches!(buffer.get(1), Some(0x08 | 0x0B | 0x2B))
+    }
+}
+
+impl From<&[u8]> for DeviceMock {
+    fn from(value: &[u8]) -> Self {
+        let req = Function::parse_request(value).unwrap();
+        DeviceMock { req }
+    }
+}
+
+impl Mock for DeviceMock {
+    fn request(&self) -> FunRequest {
+        self.req.clone()
+    }
+
+    fn response(&self) -> FunResponse {
+        let slave = self.req.slave();
+        let code = self.req.code();
+        match code {
+            // 计数器固定为 从站地址，回显为原样返回
+            FunctionCode::Diagnostics => match self.req.data()[..] {
+                [0x00, value] => Function::new(slave, code, vec![0x00, value]),
+                [sub, ..] => Function::new(slave, code, vec![sub, slave as u16]),
+                _ => self.req.clone(),
+            },
+            FunctionCode::GetCommEventCounter => Function::new(slave, code, vec![0, 100]),
+            FunctionCode::ReadDeviceIdentification => {
+                let read_code = self.req.data_u8().get(1).copied().unwrap_or(0x01);
+                let objects = [
+                    "mock".to_string(),
+                    format!("MB-{slave:02}"),
+                    "v1.0.0".to_string(),
+                ];
+
+                let mut data = vec![0x0E, read_code, 0x01, 0x00, 0x00, objects.len() as u8];
+                for (id, object) in objects.iter().enumerate() {
+                    data.push(id as u8);
+                    data.push(object.len() as u8);
+                    data.extend(object.as_bytes());
+                }
+                Function::with_bytes(slave, code, data)
+            }
+            _ => self.req.clone(),
+        }
+    }
+}
@@ -1,5 +1,14 @@
-use mb::protocol::{FunRequest, FunResponse};
+use mb::{
+    protocol::{Framing, FunRequest, FunResponse, Function, encode_frame, split_frame},
+    transport::Loopback,
+};
 
+use crate::{
+    device::DeviceMock, power::PowerMock, relay::RelayMock, temperature::TempMock,
+    voltage::VoltageMock,
+};
+
+pub mod device;
 pub mod power;
 pub mod relay;
 pub mod temperature;
@@ -9,3 +18,750 @@ pub trait Mock {
     fn request(&self) -> FunRequest;
     fn response(&self) -> FunResponse;
 }
+
+/// 按照从站地址选择模拟设备，生成响应数据
+///
+/// 无法识别的请求返回 `None`
+pub fn respond(buffer: &[u8]) -> Option<Vec<u8>> {
+    if let Err(e) = Function::parse_request(buffer) {
+        log::warn!("接收到未知请求: {buffer:02X?} {e}");
+        return None;
+    }
+
+    let mock: Box<dyn Mock> = match &buffer[0] {
+        _ if DeviceMock::accepts(buffer) => Box::new(DeviceMock::from(buffer)),
+        0x01 => Box::new(TempMock::from(buffer)),
+        0x02 => Box::new(RelayMock::from(buffer)),
+        0x03 => Box::new(PowerMock::from(buffer)),
+        0x04 => Box::new(PowerMock::from(buffer)),
+
+        // 4 之后都是电流电压
+        0x05 => Box::new(VoltageMock::from(buffer)),
+        _ => Box::new(VoltageMock::from(buffer)),
+    };
+
+    if buffer == mock.request().request_data().as_slice() {
+        let response = mock.response().response_data();
+        log::debug!("模拟设备 请求 {buffer:02X?} 响应 {response:02X?}");
+        Some(response)
+    } else {
+        log::warn!("接收到未知请求: {buffer:02X?}");
+        None
+    }
+}
+
+/// 已解析的请求转换为 RTU 后交给模拟设备处理，按照 `framing` 编码响应
+fn respond_with(framing: Framing, transaction: u16, request: &FunRequest) -> Option<Vec<u8>> {
+    let response = respond(&request.request_data())?;
+    let (slave, pdu) = split_frame(Framing::Rtu, &response).ok()?;
+    Some(encode_frame(framing, transaction, slave, pdu))
+}
+
+/// Modbus TCP 格式的请求，转换为 RTU 后交给模拟设备处理
+pub fn respond_tcp(buffer: &[u8]) -> Option<Vec<u8>> {
+    let (transaction, request) = Function::parse_request_tcp(buffer).ok()?;
+    respond_with(Framing::Tcp, transaction, &request)
+}
+
+/// Modbus ASCII 格式的请求，转换为 RTU 后交给模拟设备处理
+pub fn respond_ascii(buffer: &[u8]) -> Option<Vec<u8>> {
+    let request = Function::parse_request_ascii(buffer).ok()?;
+    respond_with(Framing::Ascii, 0, &request)
+}
+
+/// 直接连接模拟设备的内存回环
+pub fn loopback() -> Loopback {
+    Loopback::new(respond)
+}
+
+/// 直接连接模拟设备的 Modbus TCP 内存回环
+pub fn loopback_tcp() -> Loopback {
+    Loopback::new(respond_tcp)
+}
+
+/// 直接连接模拟设备的 Modbus ASCII 内存回环
+pub fn loopback_ascii() -> Loopback {
+    Loopback::new(respond_ascii)
+}
+
+#[cfg(test)]
+mod test {
+    use mb::{
+        capture::{Capture, Replay, parse_capture, read_capture},
+        codec::{self, Decoded, Decoder, Direction},
+        coil::{Coil, CoilData, CoilMode},
+        device::Device,
+        diagnostics::{
+            CommEventCounter, DeviceIdCode, DeviceInfo, Diagnostics, DiagnosticsData,
+            DiagnosticsMode,
+        },
+        error::{Error, ErrorKind},
+        policy::Policy,
+        power::{
+            OutputMode, Power, PowerCommand, PowerFeatures, PowerMode, PowerReply, PowerStatus,
+            Protection,
+        },
+        protocol::{
+            Builder, ExceptionCode, Framing, Function, FunctionCode, calculate_crc, mask_value,
+            pack_bits, unpack_bits,
+        },
+        register_map::{MapDevice, RegisterMap, WordOrder},
+        relay::{Relay, RelayMode, RelayReply},
+        scheduler::{Priority, Scheduler},
+        temperature::{
+            KeyState, RunState, Temperature, TemperatureData, TemperatureMode, TemperatureReply,
+            TemperatureStatus,
+        },
+        units::{Amps, Celsius, Volts, Watts},
+        voltage::{Voltage, VoltageCommand, VoltageData, VoltageLayout, VoltageMode},
+    };
+
+    #[test]
+    fn loopback_call() {
+        let builder = Builder::with_transport("mock", super::loopback());
+
+        let response = builder
+            .call(&Voltage::request(0x05, &VoltageMode::Read.into()))
+            .unwrap();
+        let data: VoltageData = response.try_into().unwrap();
+        assert_eq!(0x05, data.slave);
+        assert_eq!(15, data.data.len());
+
+        let request = Temperature::request(0x01, &TemperatureMode::Temp1);
+        let data: TemperatureData = builder.call(&request).unwrap().try_into().unwrap();
+        assert_eq!(Celsius::new(60.0), data.value);
+
+        let reply = builder
+            .execute::<Temperature>(0x01, &TemperatureMode::Temp1)
+            .unwrap();
+        assert!(matches!(reply, TemperatureReply::Value(data) if data.value == Celsius::new(60.0)));
+    }
+
+    #[test]
+    fn loopback_tcp_call() {
+        let builder = Builder::with_transport("mock", super::loopback_tcp()).framing(Framing::Tcp);
+
+        let response = builder
+            .call(&Voltage::request(0x05, &VoltageMode::Read.into()))
+            .unwrap();
+        let data: VoltageData = response.try_into().unwrap();
+        assert_eq!(0x05, data.slave);
+        assert_eq!(15, data.data.len());
+    }
+
+    #[test]
+    fn loopback_ascii_call() {
+        let builder =
+            Builder::with_transport("mock", super::loopback_ascii()).framing(Framing::Ascii);
+
+        let request = Temperature::request(0x01, &TemperatureMode::Temp1);
+        let data: TemperatureData = builder.call(&request).unwrap().try_into().unwrap();
+        assert_eq!(Celsius::new(60.0), data.value);
+    }
+
+    #[test]
+    fn exception_response() {
+        let loopback = mb::transport::Loopback::new(|request| {
+            let mut response = vec![request[0], request[1] | 0x80, 0x02];
+            let crc = calculate_crc(&response);
+            response.extend_from_slice(&crc.to_le_bytes());
+            Some(response)
+        });
+        let builder = Builder::with_transport("mock", loopback);
+
+        let e = builder
+            .call(&Voltage::request(0x05, &VoltageMode::Read.into()))
+            .unwrap_err();
+        match e {
+            Error::Exception {
+                slave,
+                function,
+                code,
+            } => {
+                assert_eq!(0x05, slave);
+                assert_eq!(FunctionCode::ReadInputRegisters, function);
+                assert_eq!(ExceptionCode::IllegalDataAddress, code);
+            }
+            _ => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn corrupted_response() {
+        let loopback = mb::transport::Loopback::new(|request| {
+            let mut response = super::respond(request)?;
+            response[4] ^= 0xFF;
+            Some(response)
+        });
+        let builder = Builder::with_transport("mock", loopback);
+
+        let e = builder
+            .call(&Voltage::request(0x05, &VoltageMode::Read.into()))
+            .unwrap_err();
+        assert!(matches!(e, Error::Crc(..)));
+        assert_eq!(ErrorKind::Crc, e.kind());
+
+        let loopback = mb::transport::Loopback::new(|request| {
+            let mut response = super::respond(request)?;
+            let len = response.len();
+            response[0] = 0x06;
+            let crc = calculate_crc(&response[..len - 2]);
+            response[len - 2..].copy_from_slice(&crc.to_le_bytes());
+            Some(response)
+        });
+        let builder = Builder::with_transport("mock", loopback);
+
+        let e = builder
+            .call(&Voltage::request(0x05, &VoltageMode::Read.into()))
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            Error::SlaveMismatch {
+                expected: 0x05,
+                actual: 0x06
+            }
+        ));
+    }
+
+    #[test]
+    fn write_ack() {
+        let builder = Builder::with_transport("mock", super::loopback());
+
+        let request = Relay::request(0x02, &RelayMode::ONOFF(0b0101));
+        assert_eq!(request, builder.call(&request).unwrap());
+
+        // 写入的回显不当作开关状态
+        let reply = builder.execute::<Relay>(0x02, &RelayMode::ONOFF(0b0101));
+        assert_eq!(RelayReply::Written, reply.unwrap());
+        let reply = builder.execute::<Relay>(0x02, &RelayMode::Read).unwrap();
+        assert!(matches!(reply, RelayReply::Value(_)));
+
+        // 设备回显了其他值
+        let loopback = mb::transport::Loopback::new(|request| {
+            let mut response = request.to_vec();
+            let len = response.len();
+            response[len - 3] ^= 0x01;
+            let crc = calculate_crc(&response[..len - 2]);
+            response[len - 2..].copy_from_slice(&crc.to_le_bytes());
+            Some(response)
+        });
+        let builder = Builder::with_transport("mock", loopback);
+
+        let e = builder.call(&request).unwrap_err();
+        assert!(matches!(e, Error::WriteUnconfirmed { slave: 0x02, .. }));
+
+        // 设备无响应
+        let builder = Builder::with_transport("mock", mb::transport::Loopback::new(|_| None));
+        assert!(builder.call(&request).unwrap_err().is_timeout());
+    }
+
+    #[tokio::test]
+    async fn async_loopback_call() {
+        let builder = mb::aio::AsyncBuilder::with_transport("mock", super::loopback());
+
+        let response = builder
+            .call(&Voltage::request(0x05, &VoltageMode::Read.into()))
+            .await
+            .unwrap();
+        let data: VoltageData = response.try_into().unwrap();
+        assert_eq!(0x05, data.slave);
+        assert_eq!(15, data.data.len());
+
+        let request = Temperature::request(0x01, &TemperatureMode::Temp1);
+        let data: TemperatureData = builder.call(&request).await.unwrap().try_into().unwrap();
+        assert_eq!(Celsius::new(60.0), data.value);
+    }
+
+    #[test]
+    fn respond_crafted_input() {
+        // 任意输入只返回 None，不会 panic
+        let mut seed: u32 = 0x1234_5678;
+        for len in 0..300 {
+            let data: Vec<u8> = (0..len % 64)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (seed >> 16) as u8
+                })
+                .collect();
+            let _ = super::respond(&data);
+        }
+    }
+
+    #[test]
+    fn coils() {
+        // 20 个线圈的模拟设备，响应字节数为奇数
+        let mut state = vec![false; 20];
+        let loopback = mb::transport::Loopback::new(move |request| {
+            let request = Function::parse_request(request).ok()?;
+            let data = request.data_u8();
+            let start = u16::from_be_bytes([data[0], data[1]]) as usize;
+            match request.code() {
+                FunctionCode::ReadCoils => {
+                    let response =
+                        Function::with_bytes(request.slave(), request.code(), pack_bits(&state));
+                    return Some(response.response_data());
+                }
+                FunctionCode::WriteSingleCoil => state[start] = data[2] == 0xFF,
+                FunctionCode::WriteMultipleCoils => {
+                    let count = u16::from_be_bytes([data[2], data[3]]) as usize;
+                    let values = unpack_bits(&data[5..], count);
+                    state[start..start + count].copy_from_slice(&values);
+                }
+                _ => return None,
+            }
+            Some(request.response_data())
+        });
+        let builder = Builder::with_transport("mock", loopback);
+
+        let mode = CoilMode::Write(2, true);
+        let data: CoilData = builder
+            .call(&Coil::request(0x07, &mode))
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(vec![true], data.values);
+
+        let mode = CoilMode::WriteMultiple(16, vec![true, false, true]);
+        let response = builder.call(&Coil::request(0x07, &mode)).unwrap();
+        assert_eq!(vec![0x0F, 0x00, 0x10, 0x00, 0x03], response.response_pdu());
+
+        let mode = CoilMode::Read(0, 20);
+        let request = Coil::request(0x07, &mode);
+        let data = CoilData::from_response(&mode, builder.call(&request).unwrap()).unwrap();
+        assert_eq!(20, data.values.len());
+        let on: Vec<usize> = (0..20).filter(|&i| data.get(i) == Some(true)).collect();
+        assert_eq!(vec![2, 16, 18], on);
+
+        assert_eq!(
+            vec![0x01, 0x01, 0x00, 0x00, 0x00, 0x0A, 0xBC, 0x0D],
+            Coil::request(0x01, &CoilMode::Read(0, 10)).request_data()
+        );
+    }
+
+    #[test]
+    fn mask_and_read_write() {
+        // 4 个保持寄存器的模拟设备
+        let mut registers = [0u16; 4];
+        let loopback = mb::transport::Loopback::new(move |request| {
+            let request = Function::parse_request(request).ok()?;
+            let data = request.data_u8();
+            let word = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+            match request.code() {
+                FunctionCode::MaskWriteRegister => {
+                    let register = registers.get_mut(word(0) as usize)?;
+                    *register = mask_value(*register, word(2), word(4));
+                    Some(request.response_data())
+                }
+                FunctionCode::ReadWriteMultipleRegisters => {
+                    let (read, count, write) =
+                        (word(0) as usize, word(2) as usize, word(4) as usize);
+                    for (i, value) in data[9..].chunks_exact(2).enumerate() {
+                        registers[write + i] = u16::from_be_bytes([value[0], value[1]]);
+                    }
+                    let values = registers.get(read..read + count)?.to_vec();
+                    Some(Function::new(request.slave(), request.code(), values).response_data())
+                }
+                _ => None,
+            }
+        });
+        let builder = Builder::with_transport("mock", loopback);
+
+        // 只修改指定的位
+        let request = Relay::request(0x02, &RelayMode::Switch(1, true));
+        assert_eq!(request, builder.call(&request).unwrap());
+        builder
+            .call(&Relay::request(0x02, &RelayMode::Switch(3, true)))
+            .unwrap();
+        builder
+            .call(&Relay::request(0x02, &RelayMode::Switch(1, false)))
+            .unwrap();
+
+        let request = Function::read_write_multiple(0x02, 0, 2, 2, &[0x1234]);
+        let frame = request.request_data();
+        assert!(matches!(
+            codec::decode(Framing::Rtu, Direction::Request, &frame).unwrap(),
+            Decoded::Frame(_, len) if len == frame.len()
+        ));
+        assert_eq!(vec![0b1000, 0], builder.call(&request).unwrap().data());
+
+        let request = Function::read_write_multiple(0x02, 2, 1, 3, &[1]);
+        assert_eq!(vec![0x1234], builder.call(&request).unwrap().data());
+    }
+
+    #[test]
+    fn diagnostics() {
+        let builder = Builder::with_transport("mock", super::loopback());
+
+        let request = Diagnostics::request(0x05, &DiagnosticsMode::Echo(0xA55A));
+        let data: DiagnosticsData = builder.call(&request).unwrap().try_into().unwrap();
+        assert_eq!((0x00, 0xA55A), (data.sub_function, data.value));
+
+        let request = Diagnostics::request(0x05, &DiagnosticsMode::CommEventCounter);
+        let data: CommEventCounter = builder.call(&request).unwrap().try_into().unwrap();
+        assert_eq!((false, 100), (data.busy, data.count));
+
+        let mode = DiagnosticsMode::DeviceId(DeviceIdCode::Basic, 0x00);
+        let request = Diagnostics::request(0x05, &mode);
+        assert_eq!(
+            vec![0x05, 0x2B, 0x0E, 0x01, 0x00, 0x81, 0xB7],
+            request.request_data()
+        );
+        let data: DeviceInfo = builder.call(&request).unwrap().try_into().unwrap();
+        assert_eq!(Some("mock"), data.vendor());
+        assert_eq!(Some("MB-05"), data.model());
+        assert_eq!(Some("v1.0.0"), data.revision());
+
+        // 流式解析按对象长度确定帧长
+        let frame = super::respond(&request.request_data()).unwrap();
+        let mut decoder = Decoder::new(Framing::Rtu, Direction::Response);
+        decoder.push(&frame[..9]);
+        assert!(decoder.next_frame().unwrap().is_none());
+        decoder.push(&frame[9..]);
+        assert!(decoder.next_frame().unwrap().is_some());
+    }
+
+    #[test]
+    fn bus_scheduler() {
+        use std::sync::{Arc, Mutex, mpsc};
+
+        // 第一个请求阻塞总线，其余请求排队后再按顺序发送
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let sent = order.clone();
+        let loopback = mb::transport::Loopback::new(move |request| {
+            if request[0] == 0x09 {
+                gate_rx.recv().ok();
+            }
+            sent.lock().unwrap().push(request[0]);
+            Some(request.to_vec())
+        });
+        let builder = Builder::with_transport("bus-test", loopback);
+        let scheduler = Scheduler::get(&builder.endpoint);
+        let write = |slave: u8, value: u16| {
+            Function::new(slave, FunctionCode::WriteSingleRegister, vec![0, value])
+        };
+
+        let first = scheduler.submit(&builder, &write(0x09, 0), Priority::Poll);
+        while scheduler.pending() > 0 {
+            std::thread::yield_now();
+        }
+
+        let tickets = [
+            scheduler.submit(&builder, &write(0x01, 1), Priority::Poll),
+            scheduler.submit(&builder, &write(0x01, 2), Priority::Poll),
+            scheduler.submit(&builder, &write(0x02, 1), Priority::Poll),
+            scheduler.submit(&builder, &write(0x04, 1), Priority::Background),
+        ];
+        let (done_tx, done_rx) = mpsc::channel();
+        scheduler.submit_with(
+            &builder,
+            &write(0x03, 1),
+            Priority::Control,
+            move |result| {
+                done_tx.send(result.map(|r| r.slave())).ok();
+            },
+        );
+        assert_eq!(5, scheduler.pending());
+        gate_tx.send(()).unwrap();
+
+        assert!(first.wait().is_ok());
+        for ticket in tickets {
+            assert!(ticket.wait().is_ok());
+        }
+        assert_eq!(0x03, done_rx.recv().unwrap().unwrap());
+        // 控制写入优先，轮询在从站 1、2 之间轮流，后台最后
+        assert_eq!(
+            vec![0x09, 0x03, 0x01, 0x02, 0x01, 0x04],
+            *order.lock().unwrap()
+        );
+
+        assert_eq!(Priority::Control, Priority::of(&write(0x01, 1)));
+        assert_eq!(
+            Priority::Poll,
+            Priority::of(&Voltage::request(0x05, &VoltageMode::Read.into()))
+        );
+    }
+
+    #[test]
+    fn register_map() {
+        let builder = Builder::with_transport("mock", super::loopback());
+
+        // 内置映射与原有驱动的请求一致
+        let temperature = MapDevice::new(RegisterMap::builtin("temperature").unwrap(), 0x01);
+        assert_eq!(
+            Temperature::request(0x01, &TemperatureMode::Temp1),
+            temperature.read_request("temp1").unwrap()
+        );
+        assert_eq!(
+            Temperature::request(0x01, &TemperatureMode::Set1(Celsius::new(60.0))),
+            temperature.write_request("set1", 60.0).unwrap()
+        );
+        let value = temperature.read(&builder, "temp1").unwrap();
+        assert_eq!(("°C", 60.0), (value.unit.as_str(), value.value.round()));
+        assert!(temperature.read_request("set1").is_ok());
+        assert!(temperature.write_request("temp1", 60.0).is_err());
+        assert!(temperature.read_request("temp3").is_err());
+
+        let power = MapDevice::new(RegisterMap::builtin("power").unwrap(), 0x03);
+        assert_eq!(
+            Power::request(0x03, &PowerMode::SetVoltage(Volts::new(12.5)).into()),
+            power.write_request("set_voltage", 12.5).unwrap()
+        );
+
+        // 相邻的 30 个寄存器合并为一次读取
+        let voltage = MapDevice::new(RegisterMap::builtin("voltage").unwrap(), 0x05);
+        let values = voltage.read_all(&builder).unwrap();
+        assert_eq!(30, values.len());
+        assert_eq!("ch15_current", values[29].name);
+
+        let relay = MapDevice::new(RegisterMap::builtin("relay").unwrap(), 0x02);
+        assert!(relay.write_request("state", 70000.0).is_err());
+    }
+
+    #[test]
+    fn power_status() {
+        let builder = Builder::with_transport("mock", super::loopback());
+
+        let status = match builder
+            .execute::<Power>(0x03, &PowerMode::Status.into())
+            .unwrap()
+        {
+            PowerReply::Status(status) => status,
+            reply => panic!("{reply:?}"),
+        };
+        assert_eq!(Celsius::new(25.0), status.temp);
+        assert_eq!(
+            (Volts::new(60.0), Amps::new(1.5)),
+            (status.voltage, status.current)
+        );
+        assert_eq!(Watts::new(90.0), status.power());
+        assert_eq!(
+            (Volts::new(60.0), Amps::new(2.0)),
+            (status.set_voltage, status.set_current)
+        );
+        assert!(status.output);
+        // 可选寄存器默认不读取，跳过地址 8 分两次读取
+        assert_eq!((None, None), (status.mode, status.protection));
+        let blocks = |command: &PowerCommand| -> Vec<Vec<u16>> {
+            Power::requests(0x03, command)
+                .iter()
+                .map(|request| request.data())
+                .collect()
+        };
+        assert_eq!(
+            vec![vec![2, 6], vec![9, 5]],
+            blocks(&PowerMode::Status.into())
+        );
+
+        let mut features = PowerFeatures {
+            protection: false,
+            output_mode: true,
+        };
+        let command = PowerCommand::from(PowerMode::Status).with_features(features);
+        assert_eq!(vec![vec![2, 6], vec![9, 6]], blocks(&command));
+        let status = match builder.execute::<Power>(0x03, &command).unwrap() {
+            PowerReply::Status(status) => status,
+            reply => panic!("{reply:?}"),
+        };
+        assert_eq!(
+            (Some(OutputMode::Dc), None),
+            (status.mode, status.protection)
+        );
+        assert_eq!(Volts::new(60.0), status.set_voltage);
+
+        features.protection = true;
+        let command = PowerCommand::from(PowerMode::Status).with_features(features);
+        assert_eq!(vec![vec![2, 13]], blocks(&command));
+        let status = match builder.execute::<Power>(0x03, &command).unwrap() {
+            PowerReply::Status(status) => status,
+            reply => panic!("{reply:?}"),
+        };
+        assert_eq!(Some(OutputMode::Dc), status.mode);
+        assert!(!status.protection.unwrap().any());
+
+        assert_eq!(
+            vec![9, 0x0002],
+            Power::request(0x03, &PowerMode::SetOutput(false).into()).data()
+        );
+        for mode in [
+            PowerMode::SetOutput(true),
+            PowerMode::SetOutput(false),
+            PowerMode::SetOutputMode(OutputMode::Ac),
+        ] {
+            let reply = builder.execute::<Power>(0x03, &mode.into()).unwrap();
+            assert_eq!(PowerReply::Written, reply);
+        }
+
+        let protection = Protection::from_bits(0b101);
+        assert!(protection.ovp && !protection.ocp && protection.otp);
+        assert_eq!(0b101, protection.bits());
+
+        // 无效的输出模式不影响其余读数
+        let mut data = vec![0; 13];
+        data[7] = 0x0001;
+        data[12] = 5;
+        let status = PowerStatus::decode(&data, WordOrder::ABCD, features).unwrap();
+        assert!(status.output);
+        assert_eq!(None, status.mode);
+
+        let err = PowerStatus::decode(&[0; 12], WordOrder::ABCD, features).unwrap_err();
+        assert!(matches!(err, Error::DataShort(12)));
+
+        // 分块的响应缺少一块
+        let response = Function::new(0x03, FunctionCode::ReadHoldingRegisters, vec![0; 6]);
+        let err = Power::decode(&PowerMode::Status.into(), response).unwrap_err();
+        assert!(matches!(err, Error::DataNull));
+    }
+
+    #[test]
+    fn temperature_status() {
+        let builder = Builder::with_transport("mock", super::loopback());
+
+        let status = match builder
+            .execute::<Temperature>(0x01, &TemperatureMode::Status)
+            .unwrap()
+        {
+            TemperatureReply::Status(status) => status,
+            reply => panic!("{reply:?}"),
+        };
+        assert_eq!(
+            [Celsius::new(60.0); 3],
+            [status.temp1, status.set1, status.set2]
+        );
+        assert_eq!(Celsius::new(25.0), status.temp2);
+        assert_eq!(Some(RunState::Run), status.run);
+        assert_eq!(
+            (Some(KeyState::On), Some(KeyState::On)),
+            (status.key_a, status.key_b)
+        );
+
+        // 只读取已映射的寄存器，相邻的合并
+        let blocks: Vec<Vec<u16>> = Temperature::requests(0x01, &TemperatureMode::Status)
+            .iter()
+            .map(|request| request.data())
+            .collect();
+        assert_eq!(
+            vec![
+                vec![10, 1],
+                vec![14, 1],
+                vec![46, 2],
+                vec![60, 2],
+                vec![63, 1]
+            ],
+            blocks
+        );
+
+        for mode in [
+            TemperatureMode::Run(RunState::Pause),
+            TemperatureMode::KeyB(KeyState::Off),
+            TemperatureMode::Set2(Celsius::new(45.0)),
+        ] {
+            let reply = builder.execute::<Temperature>(0x01, &mode).unwrap();
+            assert_eq!(TemperatureReply::Written, reply);
+        }
+        assert_eq!(
+            vec![63, 2],
+            Temperature::request(0x01, &TemperatureMode::Run(RunState::Pause)).data()
+        );
+
+        // 设备返回的无效运行状态和按键不影响温度
+        let mut data = vec![0; 54];
+        data[0] = 60 * 10;
+        data[46 - 10] = 5;
+        data[63 - 10] = 7;
+        let status = TemperatureStatus::decode(&data).unwrap();
+        assert_eq!(Celsius::new(60.0), status.temp1);
+        assert_eq!((None, None), (status.run, status.key_a));
+        assert_eq!(Some(KeyState::On), status.key_b);
+        assert!(matches!(
+            RunState::try_from(7).unwrap_err(),
+            Error::InvalidValue {
+                address: 63,
+                value: 7
+            }
+        ));
+        assert!(TemperatureStatus::decode(&data[..53]).is_err());
+    }
+
+    #[test]
+    fn voltage_layout() {
+        let builder = Builder::with_transport("mock", super::loopback());
+
+        let layout = VoltageLayout {
+            channels: 8,
+            register_start: 0x10,
+            ..Default::default()
+        };
+        let command = VoltageCommand::new(VoltageMode::Read, layout).unwrap();
+        let data = builder.execute::<Voltage>(0x05, &command).unwrap();
+        assert_eq!(8, data.data.len());
+    }
+
+    #[test]
+    fn capture_replay() {
+        let path = std::env::temp_dir().join(format!("mb-capture-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let policy = Policy {
+            retries: 0,
+            ..Default::default()
+        };
+        // 模拟设备不支持的请求，没有响应
+        let unknown = Function::new(0x01, FunctionCode::ReadHoldingRegisters, vec![20, 3]);
+
+        let builder = Builder::with_transport("mock", super::loopback())
+            .policy(policy)
+            .capture(Capture::create(&path).unwrap());
+        let temp = builder
+            .execute::<Temperature>(0x01, &TemperatureMode::Status)
+            .unwrap();
+        let power = builder
+            .execute::<Power>(0x03, &PowerMode::Status.into())
+            .unwrap();
+        assert!(builder.call(&unknown).unwrap_err().is_timeout());
+
+        let records = read_capture(&path).unwrap();
+        let directions: Vec<Direction> = records.iter().map(|r| r.direction).collect();
+        use Direction::{Request as Tx, Response as Rx};
+        // 温控器状态分 5 次读取，电源状态跳过地址 8 分两次读取
+        let mut expected = [Tx, Rx].repeat(7);
+        expected.push(Tx);
+        assert_eq!(expected, directions);
+        assert_eq!("mock", records[0].port);
+        assert_eq!(0x03, records[11].frame().unwrap().slave);
+
+        // 回放得到同样的解析结果
+        let replay = Replay::open(&path, Some("mock")).unwrap();
+        assert_eq!(8, replay.remaining());
+        let builder = Builder::with_transport("replay", replay).policy(policy);
+        let (TemperatureReply::Status(a), TemperatureReply::Status(b)) = (
+            temp,
+            builder
+                .execute::<Temperature>(0x01, &TemperatureMode::Status)
+                .unwrap(),
+        ) else {
+            panic!("{temp:?}");
+        };
+        assert_eq!((a.temp1, a.key_a, a.run), (b.temp1, b.key_a, b.run));
+        let (PowerReply::Status(a), PowerReply::Status(b)) = (
+            power,
+            builder
+                .execute::<Power>(0x03, &PowerMode::Status.into())
+                .unwrap(),
+        ) else {
+            panic!("{power:?}");
+        };
+        assert_eq!((a.voltage, a.current), (b.voltage, b.current));
+        assert!(builder.call(&unknown).unwrap_err().is_timeout());
+        // 记录已全部回放
+        assert!(
+            builder
+                .execute::<Temperature>(0x01, &TemperatureMode::Temp1)
+                .unwrap_err()
+                .is_timeout()
+        );
+
+        let err = parse_capture(&b"\n{\n"[..]).unwrap_err();
+        assert!(matches!(err, Error::Capture { line: 2, .. }), "{err}");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
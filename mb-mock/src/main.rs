@@ -1,9 +1,9 @@
 use std::time::Duration;
 
+use mb::codec::{Decoder, Direction, Framing};
 use mb::utils::print_hex;
-use mb_mock::{
-    power::PowerMock, relay::RelayMock, temperature::TempMock, voltage::VoltageMock, Mock,
-};
+use mb_mock::respond;
+use serialport::SerialPort;
 
 pub enum Slave {
     None,
@@ -25,38 +25,29 @@ fn main() -> std::io::Result<()> {
         .timeout(timeout)
         .open()?;
 
-    // ch340 发包限制 32为
+    // ch340 发包限制 32为，请求可能分多次到达
+    let mut decoder = Decoder::new(Framing::Rtu, Direction::Request);
 
     loop {
         let mut buffer = [0; 1024];
         match port.read(&mut buffer) {
             Ok(n) => {
-                let buffer = &buffer[..n];
-
-                let mock: Box<dyn Mock> = match &buffer[0] {
-                    0x01 => Box::new(TempMock::from(buffer)),
-                    0x02 => Box::new(RelayMock::from(buffer)),
-                    0x03 => Box::new(PowerMock::from(buffer)),
-                    0x04 => Box::new(PowerMock::from(buffer)),
-
-                    // 4 之后都是电流电压
-                    0x05 => Box::new(VoltageMock::from(buffer)),
-                    _ => Box::new(VoltageMock::from(buffer)),
-                };
-
-                if buffer == mock.request().request_data().as_slice() {
-                    print_hex("request", &buffer.to_vec());
-                    let response = mock.response().response_data();
-                    port.write_all(response.as_slice())?;
-                    print_hex("response", &response);
-
-                    port.flush().unwrap();
-                } else {
-                    println!("接收到未知请求: {:?}", buffer);
+                decoder.push(&buffer[..n]);
+                loop {
+                    match decoder.next_frame() {
+                        Ok(Some(frame)) => reply(&mut port, &frame.encode(Framing::Rtu))?,
+                        Ok(None) => break,
+                        Err(e) => eprintln!("请求解析失败: {e}"),
+                    }
                 }
             }
             Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                // 读取超时，不进行处理
+                // 读取超时视为帧结束，长度未知的请求在这里处理
+                match decoder.finish() {
+                    Ok(Some(frame)) => reply(&mut port, &frame.encode(Framing::Rtu))?,
+                    Ok(None) => {}
+                    Err(e) => eprintln!("请求解析失败: {e}"),
+                }
             }
             Err(e) => {
                 eprintln!("读取失败: {:?}", e);
@@ -65,6 +56,17 @@ fn main() -> std::io::Result<()> {
     }
 }
 
+/// 交给模拟设备处理并写回响应
+fn reply(port: &mut Box<dyn SerialPort>, request: &[u8]) -> std::io::Result<()> {
+    print_hex("request", &request.to_vec());
+    if let Some(response) = respond(request) {
+        print_hex("response", &response);
+        port.write_all(response.as_slice())?;
+        port.flush()?;
+    }
+    Ok(())
+}
+
 // [1, 4, 60,
 // 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 101, 40, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 // 84, 203]
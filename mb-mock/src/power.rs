@@ -1,36 +1,125 @@
 use crate::Mock;
 use mb::{
-    power::{f32_u16, Power, PowerMode},
-    protocol::Function,
+    device::Device,
+    power::{OutputMode, Power, PowerCommand, PowerFeatures, PowerMode, f32_u16, u16_f32},
+    protocol::{FunRequest, Function, FunctionCode},
+    register_map::WordOrder,
+    units::{Amps, Volts},
 };
 
+/// 寄存器表的起始地址
+const FIRST_ADDRESS: u16 = 2;
+/// 寄存器表的最后一个地址，输出模式
+const LAST_ADDRESS: u16 = 0x000E;
+
 pub struct PowerMock {
     slave: u8,
     mode: PowerMode,
+    features: PowerFeatures,
+    /// 收到的请求，读取时按照其中的地址和数量响应
+    request: FunRequest,
 }
 
 impl PowerMock {
     pub fn new(slave: u8, mode: PowerMode) -> Self {
-        PowerMock { slave, mode }
+        PowerMock {
+            slave,
+            mode,
+            features: PowerFeatures::default(),
+            request: Power::request(slave, &mode.into()),
+        }
+    }
+
+    pub fn mode(&self) -> PowerMode {
+        self.mode
+    }
+
+    /// 与请求一致的命令，状态按照读取的寄存器判断开启的可选寄存器
+    pub fn command(&self) -> PowerCommand {
+        PowerCommand::from(self.mode).with_features(self.features)
     }
 }
 
+/// 地址 2 ~ 14：温度 25，电压 60，电流 1.5，无保护，输出开启，设定 60V 2A，直流
+fn registers() -> Vec<u16> {
+    let mut data = Vec::new();
+    for v in [25., 60., 1.5] {
+        data.extend(f32_u16(v, WordOrder::ABCD));
+    }
+    data.extend([0x0000, 0x0003]);
+    for v in [60., 2.] {
+        data.extend(f32_u16(v, WordOrder::ABCD));
+    }
+    data.push(OutputMode::Dc as u16);
+    data
+}
+
 impl From<&[u8]> for PowerMock {
     fn from(value: &[u8]) -> Self {
-        // match value[1] {}
-        PowerMock::new(value[0], PowerMode::GetVoltage)
+        let req = match Function::parse_request(value) {
+            Ok(req) => req,
+            Err(_) => return PowerMock::new(value[0], PowerMode::GetVoltage),
+        };
+
+        let mut features = PowerFeatures::default();
+        let mode = match (req.code(), &req.data()[..]) {
+            (FunctionCode::ReadHoldingRegisters, [2, 2]) => PowerMode::Temp,
+            (FunctionCode::ReadHoldingRegisters, [4, 2]) => PowerMode::Voltage,
+            (FunctionCode::ReadHoldingRegisters, [6, 2]) => PowerMode::Current,
+            (FunctionCode::ReadHoldingRegisters, [9, 3]) => PowerMode::GetOnOff,
+            (FunctionCode::ReadHoldingRegisters, [0x000A, 2]) => PowerMode::GetVoltage,
+            (FunctionCode::ReadHoldingRegisters, [0x000C, 2]) => PowerMode::GetCurrent,
+            // 状态的各个分块
+            (FunctionCode::ReadHoldingRegisters, [address, words])
+                if (FIRST_ADDRESS..=LAST_ADDRESS).contains(address)
+                    && *words <= LAST_ADDRESS + 1 - address =>
+            {
+                features.protection = *address == 2 && *words >= 12;
+                features.output_mode = address + words > LAST_ADDRESS;
+                PowerMode::Status
+            }
+            (FunctionCode::WriteMultipleRegisters, [9, value]) => {
+                PowerMode::SetOutput(value & 0x0001 != 0)
+            }
+            (FunctionCode::WriteMultipleRegisters, [0x000E, value]) => {
+                PowerMode::SetOutputMode(OutputMode::try_from(*value).unwrap_or_default())
+            }
+            (FunctionCode::WriteMultipleRegisters, [0x000A, w0, w1]) => {
+                PowerMode::SetVoltage(Volts::new(u16_f32([*w0, *w1], WordOrder::ABCD)))
+            }
+            (FunctionCode::WriteMultipleRegisters, [0x000C, w0, w1]) => {
+                PowerMode::SetCurrent(Amps::new(u16_f32([*w0, *w1], WordOrder::ABCD)))
+            }
+            _ => return PowerMock::new(req.slave(), PowerMode::GetVoltage),
+        };
+        PowerMock {
+            slave: req.slave(),
+            mode,
+            features,
+            request: req,
+        }
     }
 }
 
 impl Mock for PowerMock {
     fn request(&self) -> Function {
-        Power::request(self.slave, &self.mode)
+        self.request.clone()
     }
 
     fn response(&self) -> Function {
-        let mode = self.mode.params();
-
-        let data = f32_u16(60.);
-        Function::new(self.slave, mode.0, data.to_vec())
+        let code = self.request.code();
+        match (code, &self.request.data()[..]) {
+            (FunctionCode::ReadHoldingRegisters, [address, words]) => {
+                let start = address.saturating_sub(FIRST_ADDRESS) as usize;
+                let data = registers()
+                    .into_iter()
+                    .skip(start)
+                    .take(*words as usize)
+                    .collect();
+                Function::new(self.slave, code, data)
+            }
+            // 写入为确认
+            _ => self.request.clone(),
+        }
     }
 }
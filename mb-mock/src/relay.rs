@@ -2,7 +2,8 @@ use std::sync::{Mutex, OnceLock};
 
 use crate::Mock;
 use mb::{
-    protocol::{FunRequest, FunResponse, Function, FunctionCode},
+    device::Device,
+    protocol::{FunRequest, FunResponse, Function, FunctionCode, mask_value},
     relay::{Relay, RelayData, RelayMode},
 };
 use rand::Rng;
@@ -39,13 +40,24 @@ impl From<&[u8]> for RelayMock {
 
         match req.code() {
             FunctionCode::ReadHoldingRegisters => RelayMock::new(value[0], RelayMode::Read),
+            FunctionCode::MaskWriteRegister => {
+                let mut data = fake_data().lock().unwrap();
+                if let [_, and_mask, or_mask] = req.data()[..] {
+                    *data = mask_value(*data, and_mask, or_mask);
+                }
+                let mut mock = RelayMock::new(value[0], RelayMode::ONOFF(0));
+                mock.set_req(req);
+                mock
+            }
             _ => {
                 // 解析请求
 
                 let mut data = fake_data().lock().unwrap();
                 let req_data = req.data();
                 // 因为为请求数据 0 为线圈， 1 为数据
-                *data = req_data[1];
+                if let Some(&value) = req_data.get(1) {
+                    *data = value;
+                }
                 let mut mock = RelayMock::new(value[0], RelayMode::ONOFF(0));
                 mock.set_req(req);
                 mock
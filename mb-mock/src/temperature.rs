@@ -1,7 +1,9 @@
 use crate::Mock;
 use mb::{
+    device::Device,
     protocol::{Function, FunctionCode},
-    temperature::{Temperature, TemperatureMode},
+    temperature::{KeyState, RunState, Temperature, TemperatureMode},
+    units::Celsius,
 };
 
 pub struct TempMock {
@@ -22,41 +24,99 @@ impl TempMock {
     pub fn set_fc(&mut self, req: Function) {
         self.req = Some(req);
     }
+
+    pub fn mode(&self) -> TemperatureMode {
+        self.mode
+    }
 }
 
 impl From<&[u8]> for TempMock {
     fn from(value: &[u8]) -> Self {
         let req = Function::parse_request(value).unwrap();
 
-        match req.code() {
-            FunctionCode::ReadHoldingRegisters => {
-                if req.data()[0] == 10 {
-                    TempMock::new(req.slave(), TemperatureMode::Temp1)
-                } else {
-                    TempMock::new(req.slave(), TemperatureMode::Temp2)
-                }
+        match (req.code(), &req.data()[..]) {
+            (FunctionCode::ReadHoldingRegisters, [10, 1]) => {
+                TempMock::new(req.slave(), TemperatureMode::Temp1)
+            }
+            (FunctionCode::ReadHoldingRegisters, [14, 1]) => {
+                TempMock::new(req.slave(), TemperatureMode::Temp2)
             }
-            FunctionCode::WriteSingleRegister => {
-                let mut mock = TempMock::new(req.slave(), TemperatureMode::Run(0));
+            // 状态的其余分组，只响应已映射的寄存器
+            (FunctionCode::ReadHoldingRegisters, &[address, words])
+                if (0..words).all(|i| MAPPED.contains(&address.wrapping_add(i))) =>
+            {
+                let mut mock = TempMock::new(req.slave(), TemperatureMode::Status);
                 mock.set_fc(req);
                 mock
             }
-            _ => todo!(),
+            (FunctionCode::WriteSingleRegister, &[address, value]) => {
+                let key = |value| match value {
+                    0 => Some(KeyState::On),
+                    1 => Some(KeyState::Off),
+                    _ => None,
+                };
+                let mode = match address {
+                    60 => Some(TemperatureMode::Set1(Celsius::from_deci(value))),
+                    61 => Some(TemperatureMode::Set2(Celsius::from_deci(value))),
+                    63 => RunState::try_from(value).ok().map(TemperatureMode::Run),
+                    46 => key(value).map(TemperatureMode::KeyA),
+                    47 => key(value).map(TemperatureMode::KeyB),
+                    _ => None,
+                };
+
+                // 无效的值，生成的请求不会匹配
+                let mut mock = TempMock::new(req.slave(), mode.unwrap_or(TemperatureMode::Temp1));
+                if mode.is_some() {
+                    mock.set_fc(req);
+                }
+                mock
+            }
+            // 不支持的命令，生成的请求不会匹配
+            _ => TempMock::new(req.slave(), TemperatureMode::Temp1),
         }
     }
 }
 
+/// 寄存器表的起始地址
+const FIRST_ADDRESS: u16 = 10;
+/// 寄存器表的最后一个地址，运行状态
+const LAST_ADDRESS: u16 = 63;
+/// 温控器的寄存器，其余地址不响应
+const MAPPED: [u16; 7] = [10, 14, 46, 47, 60, 61, 63];
+
+/// 地址 10 ~ 63：温度 1 60 度，温度 2 25 度，设定 60 度，运行中
+fn registers() -> Vec<u16> {
+    let mut data = vec![0; (LAST_ADDRESS - FIRST_ADDRESS + 1) as usize];
+    let mut set = |address: u16, value: u16| data[(address - FIRST_ADDRESS) as usize] = value;
+    set(10, 60 * 10);
+    set(14, 25 * 10);
+    set(60, 60 * 10);
+    set(61, 60 * 10);
+    set(63, RunState::Run as u16);
+    data
+}
+
 impl Mock for TempMock {
     fn request(&self) -> Function {
-        Temperature::request(self.slave, &self.mode)
+        match &self.req {
+            Some(req) => req.clone(),
+            None => Temperature::request(self.slave, &self.mode),
+        }
     }
 
     fn response(&self) -> Function {
-        let (mode, _data) = self.mode.params();
-        if mode == FunctionCode::ReadHoldingRegisters {
-            Function::new(self.slave, mode, vec![60 * 10])
-        } else {
-            self.req.clone().unwrap()
+        let request = self.request();
+        match (request.code(), &request.data()[..]) {
+            (FunctionCode::ReadHoldingRegisters, &[address, words]) => {
+                let data = registers()
+                    .into_iter()
+                    .skip(address.saturating_sub(FIRST_ADDRESS) as usize)
+                    .take(words as usize)
+                    .collect();
+                Function::new(self.slave, request.code(), data)
+            }
+            // 写入为确认
+            _ => request,
         }
     }
 }
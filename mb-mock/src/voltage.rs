@@ -1,6 +1,7 @@
 use mb::{
+    device::Device,
     protocol::{Function, FunctionCode},
-    voltage::Voltage,
+    voltage::{Voltage, VoltageCommand, VoltageLayout, VoltageMode},
 };
 use rand::Rng;
 
@@ -8,29 +9,50 @@ use crate::Mock;
 
 pub struct VoltageMock {
     slave: u8,
+    layout: VoltageLayout,
 }
 
 impl VoltageMock {
     pub fn new(slave: u8) -> Self {
-        VoltageMock { slave }
+        VoltageMock {
+            slave,
+            layout: VoltageLayout::default(),
+        }
+    }
+
+    /// 按照请求推断的读取命令，不检查布局
+    pub fn command(&self) -> VoltageCommand {
+        VoltageCommand {
+            mode: VoltageMode::Read,
+            layout: self.layout.clone(),
+        }
     }
 }
 
+/// 按照请求的起始地址和数量确定通道
 impl From<&[u8]> for VoltageMock {
     fn from(value: &[u8]) -> Self {
-        VoltageMock::new(value[0])
+        let mut mock = VoltageMock::new(value[0]);
+        let data = Function::parse_request(value)
+            .map(|req| req.data())
+            .unwrap_or_default();
+        if let [start, words] = data[..] {
+            mock.layout.register_start = start;
+            mock.layout.channels = words as usize / 2;
+        }
+        mock
     }
 }
 
 impl Mock for VoltageMock {
     fn request(&self) -> Function {
         // let request: [u8; 8] = [0x01, 0x04, 0x00, 0x00, 0x00, 0x1E, 0x70, 0x02];
-        Voltage::request(self.slave)
+        Voltage::request(self.slave, &self.command())
     }
 
     fn response(&self) -> Function {
         let code = FunctionCode::ReadInputRegisters;
-        let data = generate_response_voltage();
+        let data = generate_response_voltage(self.layout.channels);
         // let data = static_response();
 
         Function::new(self.slave, code, data)
@@ -64,13 +86,13 @@ fn static_response() -> Vec<u16> {
 }
 
 #[allow(dead_code)]
-fn generate_response_voltage() -> Vec<u16> {
+fn generate_response_voltage(channels: usize) -> Vec<u16> {
     let mut rng = rand::thread_rng();
 
-    // 生成 15 对电压和电流数据
-    let mut data: Vec<u16> = Vec::with_capacity(30);
+    // 生成 channels 对电压和电流数据
+    let mut data: Vec<u16> = Vec::with_capacity(channels * 2);
 
-    (0..=14)
+    (0..channels)
         .map(|_| {
             let voltage = match rng.gen_range(0..3) {
                 0 => rng.gen_range(0..25),
@@ -98,7 +120,7 @@ mod test {
 
     #[test]
     fn gen_data() {
-        let r = super::generate_response_voltage();
+        let r = super::generate_response_voltage(15);
         // assert_eq!(30, r.len());
         println!("{:?}", r);
         r.iter().for_each(|x| print!("{:02X} ", x));
@@ -1,12 +1,16 @@
 #![allow(dead_code)]
 
 use mb::{
-    power::PowerData,
-    protocol::Builder,
-    relay::{RelayData, RelayMode},
-    temperature::{TemperatureData, TemperatureMode},
+    capture::{read_capture, CaptureRecord, Replay},
+    codec::Direction,
+    device::Device,
+    policy::Policy,
+    power::{Power, PowerMode},
+    protocol::{Builder, FunRequest, FunResponse, Function, FunctionCode},
+    relay::{Relay, RelayData, RelayMode},
+    temperature::{Temperature, TemperatureData, TemperatureMode},
     utils::print_hex,
-    voltage::VoltageData,
+    voltage::{Voltage, VoltageData},
     Result,
 };
 use mb_mock::{
@@ -14,6 +18,18 @@ use mb_mock::{
 };
 
 fn main() -> Result<()> {
+    // mb-read replay <记录文件> [端口]
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("replay") {
+        return match args.get(1) {
+            Some(path) => replay(path, args.get(2).map(String::as_str)),
+            None => {
+                println!("用法: mb-read replay <记录文件> [端口]");
+                Ok(())
+            }
+        };
+    }
+
     let req = MockReq::new();
 
     // req.run_temp(TempMock::new(0x01, TemperatureMode::Temp1))?;
@@ -22,7 +38,7 @@ fn main() -> Result<()> {
     req.run_relay(RelayMock::new(0x02, RelayMode::ON(0, 1)))?;
     // req.run_relay(RelayMock::new(0x02, RelayMode::Read))?;
 
-    // req.run_power(PowerMock::new(0x03, mb::power::PowerMode::SetOnOff))?;
+    // req.run_power(PowerMock::new(0x03, mb::power::PowerMode::SetOutput(true)))?;
     // req.run_power(PowerMock::new(0x03, mb::power::PowerMode::GetVoltage))?;
 
     // req.run_voltage(VoltageMock::new(0x05))?;
@@ -46,8 +62,110 @@ impl MockReq {
         (run_voltage, VoltageMock, VoltageData),
         (run_temp, TempMock, TemperatureData),
         (run_relay, RelayMock, RelayData),
-        (run_power, PowerMock, PowerData),
     ];
+
+    /// 电源的读数类型由命令决定
+    fn run_power(&self, mock: PowerMock) -> Result<()> {
+        println!("\n----\nstart run_power: \n");
+
+        let request = mock.request();
+        print_hex("request", &request.request_data());
+
+        let response = self.builder.call(&request)?;
+        print_hex("response", &response.response_data());
+
+        println!("u16:\n{:?}", response.data());
+        let reply = Power::decode(&mock.command(), response)?;
+        println!("解析结果:\n{:?}", reply);
+
+        Ok(())
+    }
+}
+
+/// 回放记录文件，端口为空时回放全部端口
+///
+/// 每条请求和之后的响应单独回放，解析失败的请求不影响后面的记录
+fn replay(path: &str, port: Option<&str>) -> Result<()> {
+    let records: Vec<CaptureRecord> = read_capture(path)?
+        .into_iter()
+        .filter(|r| port.is_none_or(|port| r.port == port))
+        .collect();
+
+    let policy = Policy {
+        retries: 0,
+        ..Default::default()
+    };
+
+    let mut index = 0;
+    while index < records.len() {
+        let tx = &records[index];
+        let end = records[index + 1..]
+            .iter()
+            .position(|r| r.direction == Direction::Request)
+            .map_or(records.len(), |n| index + 1 + n);
+        let group = &records[index..end];
+        index = end;
+
+        if tx.direction != Direction::Request {
+            println!("跳过第一条请求之前的数据: {:02X?}", tx.data);
+            continue;
+        }
+
+        println!("\n----\n{} {:?} {}", tx.port, tx.time, tx.framing);
+        print_hex("request", &tx.data);
+        let request = match Function::parse_request_frame(tx.framing, &tx.data) {
+            Ok(request) => request,
+            Err(e) => {
+                println!("请求解析失败: {e}");
+                continue;
+            }
+        };
+
+        let builder = Builder::with_transport(&tx.port, Replay::new(group.to_vec()))
+            .framing(tx.framing)
+            .policy(policy);
+        match builder.call(&request) {
+            Ok(response) if response == request => println!("命令执行"),
+            Ok(response) => {
+                print_hex("response", &response.response_data());
+                println!("u16:\n{:?}", response.data());
+                match describe(&request, response) {
+                    Ok(text) => println!("解析结果:\n{text}"),
+                    Err(e) => println!("解析失败: {e}"),
+                }
+            }
+            Err(e) => println!("响应失败: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// 按照模拟设备的从站地址约定解析响应
+fn describe(request: &FunRequest, response: FunResponse) -> Result<String> {
+    let buffer = request.request_data();
+    Ok(match request.slave() {
+        0x01 => {
+            let mode = TempMock::from(&buffer[..]).mode();
+            format!("{:?}", Temperature::decode(&mode, response)?)
+        }
+        0x02 => {
+            // 写入的回显不是开关状态，只需要区分读写
+            let mode = match request.code() {
+                FunctionCode::ReadHoldingRegisters => RelayMode::Read,
+                _ => RelayMode::ONOFF(0),
+            };
+            format!("{:?}", Relay::decode(&mode, response)?)
+        }
+        0x03 | 0x04 => {
+            let command = PowerMock::from(&buffer[..]).command();
+            format!("{:?}", Power::decode(&command, response)?)
+        }
+        _ => {
+            let command = VoltageMock::from(&buffer[..]).command();
+            format!("{:?}", Voltage::decode(&command, response)?)
+        }
+    })
 }
 
 #[macro_export]
@@ -144,7 +262,7 @@ fn run_relay(builder: &Builder, slave: u8) -> Result<()> {
 fn run_power(builder: &Builder, slave: u8) -> Result<()> {
     println!("\n----\nstart power: \n");
 
-    let mock = PowerMock::new(slave, mb::power::PowerMode::GetVoltage);
+    let mock = PowerMock::new(slave, PowerMode::GetVoltage);
 
     let request = mock.request();
     print_hex("request", &request.request_data());
@@ -158,8 +276,8 @@ fn run_power(builder: &Builder, slave: u8) -> Result<()> {
     }
 
     println!("u16:\n{:?}", response.data());
-    let data: PowerData = response.try_into()?;
-    println!("解析结果:\n{:?}", data);
+    let reply = Power::decode(&mock.command(), response)?;
+    println!("解析结果:\n{:?}", reply);
 
     Ok(())
 }
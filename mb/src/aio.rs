@@ -0,0 +1,389 @@
+//! 异步客户端
+//!
+//! 基于 tokio 的 [`AsyncBuilder`]，请求的生成与响应的解析和同步的 [`Builder`](crate::protocol::Builder) 相同，
+//! 设备请求（例如 [`Voltage::request`](crate::device::Device::request)）可以直接使用，
+//! 也可以使用 [`AsyncBuilder::execute`] 发送设备命令。
+//!
+//! 丢弃 `call` 返回的 future 即取消请求，未完成的收发会在下次请求前重新打开连接。
+
+use std::{fmt, io, sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::Mutex,
+    time::{Instant, sleep, timeout},
+};
+use tokio_serial::{ClearBuffer, SerialPort, SerialPortBuilderExt, SerialStream};
+
+use crate::{
+    Result,
+    codec::{Framing, MBAP_LEN, Mbap, rtu_response_len},
+    connection::is_reconnect_error,
+    device::Device,
+    error::Error,
+    policy::Policy,
+    protocol::{FunRequest, FunResponse, endpoint_gap, next_transaction},
+    transport::{Endpoint, Loopback},
+};
+
+/// 异步数据传输
+pub trait AsyncTransport: AsyncRead + AsyncWrite + Send + Unpin {
+    /// 丢弃接收缓存中残留的数据
+    fn clear_input(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncTransport for SerialStream {
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.clear(ClearBuffer::Input)?;
+        Ok(())
+    }
+}
+
+impl AsyncTransport for TcpStream {
+    fn clear_input(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 256];
+        loop {
+            match self.try_read(&mut buffer) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "连接已关闭",
+                    ));
+                }
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl AsyncTransport for Loopback {
+    fn clear_input(&mut self) -> io::Result<()> {
+        crate::transport::Transport::clear_input(self)
+    }
+}
+
+/// 打开异步传输
+async fn open(endpoint: &Endpoint, wait: Duration) -> Result<Box<dyn AsyncTransport>> {
+    match endpoint {
+        Endpoint::Serial {
+            port_name,
+            baudrate,
+        } => {
+            let port = tokio_serial::new(port_name.clone(), *baudrate).open_native_async()?;
+            Ok(Box::new(port))
+        }
+        Endpoint::Tcp { addr } => {
+            let stream = timeout(wait, TcpStream::connect(addr.as_str()))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "连接超时"))??;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        }
+        Endpoint::Custom { name } => Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotConnected,
+            format!("传输 {name} 无法重新打开"),
+        ))),
+    }
+}
+
+/// 异步连接
+struct AsyncConnection {
+    endpoint: Endpoint,
+    transport: Option<Box<dyn AsyncTransport>>,
+    /// 上次收发没有完成（请求被取消）
+    dirty: bool,
+    last_used: Instant,
+}
+
+impl AsyncConnection {
+    fn close(&mut self) {
+        if self.transport.take().is_some() {
+            log::debug!("关闭连接: {}", self.endpoint);
+        }
+    }
+
+    async fn open(&mut self, wait: Duration) -> Result<()> {
+        if self.dirty {
+            self.dirty = false;
+            match self.endpoint {
+                // 自定义传输无法重新打开，只清空缓存
+                Endpoint::Custom { .. } => {
+                    if let Some(transport) = self.transport.as_mut() {
+                        transport.clear_input()?;
+                    }
+                }
+                _ => self.close(),
+            }
+        }
+
+        if self.transport.is_none() {
+            log::debug!("打开连接: {}", self.endpoint);
+            self.transport = Some(open(&self.endpoint, wait).await?);
+        }
+
+        Ok(())
+    }
+
+    /// 发送一帧并读取响应
+    async fn exchange(
+        &mut self,
+        frame: &[u8],
+        framing: Framing,
+        wait: Duration,
+        gap: Duration,
+        transaction: u16,
+    ) -> Result<Vec<u8>> {
+        self.open(wait).await?;
+        let Some(port) = self.transport.as_mut() else {
+            return Err(Error::Io(io::Error::from(io::ErrorKind::NotConnected)));
+        };
+
+        // 在完成之前被取消时，下次请求重新打开连接
+        self.dirty = true;
+
+        port.clear_input()?;
+        port.write_all(frame).await?;
+        port.flush().await?;
+
+        let response = match framing {
+            Framing::Rtu | Framing::RtuOverTcp => read_rtu_frame(port, wait, gap).await,
+            Framing::Ascii => read_ascii_frame(port, wait).await,
+            Framing::Tcp => read_mbap_frame(port, wait, transaction).await,
+        };
+
+        self.dirty = false;
+        response
+    }
+}
+
+impl fmt::Debug for AsyncConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncConnection")
+            .field("endpoint", &self.endpoint)
+            .field("open", &self.transport.is_some())
+            .finish()
+    }
+}
+
+/// 异步 Builder，克隆后共享同一个连接
+#[derive(Debug, Clone)]
+pub struct AsyncBuilder {
+    pub endpoint: Endpoint,
+    pub framing: Framing,
+    pub policy: Policy,
+    conn: Arc<Mutex<AsyncConnection>>,
+}
+
+impl AsyncBuilder {
+    fn with_endpoint(endpoint: Endpoint, transport: Option<Box<dyn AsyncTransport>>) -> Self {
+        let framing = match endpoint {
+            Endpoint::Tcp { .. } => Framing::Tcp,
+            _ => Framing::Rtu,
+        };
+
+        let conn = AsyncConnection {
+            endpoint: endpoint.clone(),
+            transport,
+            dirty: false,
+            last_used: Instant::now(),
+        };
+
+        Self {
+            endpoint,
+            framing,
+            policy: Policy::default(),
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    /// 串口，`tcp://host:port` 形式的名称使用 Modbus TCP
+    pub fn new<T: AsRef<str>>(port_name: T, baudrate: u32) -> Self {
+        Self::with_endpoint(Endpoint::parse(port_name, baudrate), None)
+    }
+
+    /// Modbus TCP `host:port`
+    pub fn tcp<T: Into<String>>(addr: T) -> Self {
+        Self::with_endpoint(Endpoint::tcp(addr), None)
+    }
+
+    /// 使用自定义传输，例如 [`Loopback`]
+    pub fn with_transport<N, T>(name: N, transport: T) -> Self
+    where
+        N: Into<String>,
+        T: AsyncTransport + 'static,
+    {
+        let endpoint = Endpoint::Custom { name: name.into() };
+        Self::with_endpoint(endpoint, Some(Box::new(transport)))
+    }
+
+    /// 设定帧格式
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// 设定请求策略
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// 发送设备命令并解析响应
+    pub async fn execute<D: Device>(&self, slave: u8, command: &D::Command) -> Result<D::Output> {
+        let mut responses = Vec::new();
+        for request in D::requests(slave, command) {
+            responses.push(self.call(&request).await?);
+        }
+        D::decode_all(command, responses)
+    }
+
+    /// 发送请求，读取数据后，将数据转化
+    ///
+    /// 失败时按照 [`Policy`] 退避后重试，丢弃返回的 future 即取消请求
+    pub async fn call(&self, request: &FunRequest) -> Result<FunResponse> {
+        let mut attempt = 0;
+        loop {
+            match self.call_once(request).await {
+                Err(e)
+                    if attempt < self.policy.retries && self.policy.should_retry(request, &e) =>
+                {
+                    attempt += 1;
+                    log::warn!(
+                        "{} 从站 {} 请求失败，第 {attempt} 次重试: {e}",
+                        self.endpoint,
+                        request.slave()
+                    );
+                    sleep(self.policy.backoff(attempt)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn call_once(&self, request: &FunRequest) -> Result<FunResponse> {
+        let framing = self.framing;
+        let wait = self.policy.timeout();
+        let gap = endpoint_gap(&self.endpoint);
+        let transaction = next_transaction();
+        let frame = request.request_frame(framing, transaction);
+
+        let mut conn = self.conn.lock().await;
+
+        let delay = self.policy.delay();
+        let elapsed = conn.last_used.elapsed();
+        if elapsed < delay {
+            sleep(delay - elapsed).await;
+        }
+
+        let result = conn.exchange(&frame, framing, wait, gap, transaction).await;
+        conn.last_used = Instant::now();
+
+        let response = match result {
+            Err(e) if is_reconnect_error(&e) => {
+                log::warn!("{} 读写失败，关闭连接: {e}", self.endpoint);
+                conn.close();
+                return Err(e);
+            }
+            result => result?,
+        };
+        drop(conn);
+
+        request.decode_response(framing, &response)
+    }
+}
+
+/// 读取一次数据，超时返回 `None`
+async fn read_some(
+    port: &mut Box<dyn AsyncTransport>,
+    buffer: &mut [u8],
+    wait: Duration,
+) -> io::Result<Option<usize>> {
+    match timeout(wait, port.read(buffer)).await {
+        Ok(Ok(n)) => Ok(Some(n)),
+        Ok(Err(e)) if e.kind() == io::ErrorKind::TimedOut => Ok(None),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Ok(None),
+    }
+}
+
+/// 读取一帧 RTU 响应，与同步版本相同按照长度或帧间静默判断结束
+async fn read_rtu_frame(
+    port: &mut Box<dyn AsyncTransport>,
+    wait: Duration,
+    gap: Duration,
+) -> Result<Vec<u8>> {
+    let mut frame = Vec::with_capacity(256);
+    let mut buffer = [0u8; 256];
+
+    loop {
+        let len = rtu_response_len(&frame);
+        if let Some(len) = len
+            && frame.len() >= len
+        {
+            frame.truncate(len);
+            break;
+        }
+
+        let wait = if len.is_none() { gap } else { wait };
+        match read_some(port, &mut buffer, wait).await? {
+            Some(0) => break,
+            Some(n) => frame.extend_from_slice(&buffer[..n]),
+            None if frame.is_empty() => return Err(Error::Timeout),
+            // 帧不完整时交给 CRC 校验报告
+            None => break,
+        }
+    }
+
+    Ok(frame)
+}
+
+/// 读取 ASCII 帧，直到 LF
+async fn read_ascii_frame(port: &mut Box<dyn AsyncTransport>, wait: Duration) -> Result<Vec<u8>> {
+    let mut frame = Vec::new();
+    let mut buffer = [0u8; 64];
+
+    while !frame.contains(&b'\n') {
+        match read_some(port, &mut buffer, wait).await? {
+            None if frame.is_empty() => return Err(Error::Timeout),
+            Some(0) | None => break,
+            Some(n) => frame.extend_from_slice(&buffer[..n]),
+        }
+    }
+
+    Ok(frame)
+}
+
+/// 按照 MBAP 头中的长度读取一帧，丢弃事务号不匹配的旧响应
+async fn read_mbap_frame(
+    port: &mut Box<dyn AsyncTransport>,
+    wait: Duration,
+    transaction: u16,
+) -> Result<Vec<u8>> {
+    loop {
+        let mut frame = vec![0u8; MBAP_LEN];
+        timeout(wait, port.read_exact(&mut frame))
+            .await
+            .map_err(|_| Error::Timeout)??;
+
+        let header = Mbap::parse(&frame)?;
+        let mut pdu = vec![0u8; header.pdu_len()];
+        timeout(wait, port.read_exact(&mut pdu))
+            .await
+            .map_err(|_| Error::Timeout)??;
+        frame.extend_from_slice(&pdu);
+
+        if header.transaction == transaction {
+            return Ok(frame);
+        }
+
+        log::warn!(
+            "丢弃事务号不匹配的响应: 期望 {transaction}, 实际 {}",
+            header.transaction
+        );
+    }
+}
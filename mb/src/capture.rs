@@ -0,0 +1,379 @@
+//! 报文记录与回放
+//!
+//! [`Builder::capture`](crate::protocol::Builder::capture) 把每次收发的原始数据按照 JSON lines 写入文件，
+//! 每行一条 [`CaptureRecord`]：
+//!
+//! ```text
+//! {"time":{"secs":1760000000,"nanos":0},"port":"COM3@9600","framing":"Rtu","direction":"tx","data":"01 03 00 0A 00 01 A4 08"}
+//! {"time":{"secs":1760000000,"nanos":5000000},"port":"COM3@9600","framing":"Rtu","direction":"rx","data":"01 03 02 02 58 B8 DE"}
+//! ```
+//!
+//! [`Replay`] 是按照记录回放响应的传输，现场的问题可以离线使用同样的解析和判定逻辑复现：
+//!
+//! ```
+//! use mb::{
+//!     capture::{CaptureRecord, Replay},
+//!     codec::Direction,
+//!     protocol::{Builder, Framing},
+//!     temperature::{Temperature, TemperatureMode, TemperatureReply},
+//!     units::Celsius,
+//! };
+//!
+//! let record = |direction, data: &[u8]| CaptureRecord {
+//!     time: Default::default(),
+//!     port: "COM3@9600".to_string(),
+//!     framing: Framing::Rtu,
+//!     direction,
+//!     data: data.to_vec(),
+//! };
+//! let records = [
+//!     record(Direction::Request, &[0x01, 0x03, 0x00, 0x0A, 0x00, 0x01, 0xA4, 0x08]),
+//!     record(Direction::Response, &[0x01, 0x03, 0x02, 0x02, 0x58, 0xB8, 0xDE]),
+//! ];
+//!
+//! let builder = Builder::with_transport("replay", Replay::new(records));
+//! let reply = builder.execute::<Temperature>(0x01, &TemperatureMode::Temp1).unwrap();
+//! assert!(matches!(reply, TemperatureReply::Value(data) if data.value == Celsius::new(60.0)));
+//! ```
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Result,
+    codec::{Direction, Frame, Framing, decode_frame},
+    connection::lock,
+    error::Error,
+    transport::Transport,
+    utils::current_timestamp,
+};
+
+/// 一次发送或接收的原始数据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    pub time: Duration,
+    /// 连接目标，见 [`Endpoint`](crate::transport::Endpoint)
+    pub port: String,
+    pub framing: Framing,
+    pub direction: Direction,
+    /// 十六进制，空格分隔
+    #[serde(with = "hex")]
+    pub data: Vec<u8>,
+}
+
+impl CaptureRecord {
+    pub fn new<T: Into<String>>(
+        port: T,
+        framing: Framing,
+        direction: Direction,
+        data: Vec<u8>,
+    ) -> Self {
+        Self {
+            time: current_timestamp(),
+            port: port.into(),
+            framing,
+            direction,
+            data,
+        }
+    }
+
+    /// 按照帧格式解析，不完整或校验失败时返回错误
+    pub fn frame(&self) -> Result<Frame> {
+        decode_frame(self.framing, &self.data)
+    }
+}
+
+/// 读取记录文件
+pub fn read_capture<P: AsRef<Path>>(path: P) -> Result<Vec<CaptureRecord>> {
+    parse_capture(BufReader::new(File::open(path)?))
+}
+
+/// 逐行解析记录，跳过空行
+pub fn parse_capture<R: BufRead>(reader: R) -> Result<Vec<CaptureRecord>> {
+    let mut records = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record = serde_json::from_str(&line).map_err(|source| Error::Capture {
+            line: index + 1,
+            source,
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// 记录文件，多个 [`Builder`](crate::protocol::Builder) 可以共享同一个文件
+#[derive(Clone)]
+pub struct Capture {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Capture {
+    /// 追加到文件，不存在时创建
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::with_writer(file))
+    }
+
+    pub fn with_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    /// 写入一行，失败时只记录日志，不影响收发
+    pub fn record(&self, record: &CaptureRecord) {
+        let result = serde_json::to_vec(record)
+            .map_err(io::Error::from)
+            .and_then(|mut line| {
+                line.push(b'\n');
+                let mut writer = lock(&self.writer);
+                writer.write_all(&line)?;
+                writer.flush()
+            });
+
+        if let Err(e) = result {
+            log::warn!("报文记录写入失败: {e}");
+        }
+    }
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Capture").finish_non_exhaustive()
+    }
+}
+
+/// 记录经过的数据：`flush` 时写入发送的请求，[`Recorder::finish`] 时写入收到的响应
+pub(crate) struct Recorder<'a> {
+    inner: &'a mut dyn Transport,
+    capture: &'a Capture,
+    port: String,
+    framing: Framing,
+    tx: Vec<u8>,
+    rx: Vec<u8>,
+    /// 收到第一个字节的时间
+    rx_time: Duration,
+}
+
+impl<'a> Recorder<'a> {
+    pub(crate) fn new(
+        inner: &'a mut dyn Transport,
+        capture: &'a Capture,
+        port: String,
+        framing: Framing,
+    ) -> Self {
+        Self {
+            inner,
+            capture,
+            port,
+            framing,
+            tx: Vec::new(),
+            rx: Vec::new(),
+            rx_time: Duration::ZERO,
+        }
+    }
+
+    /// 写入收到的数据，超时没有数据时不记录
+    pub(crate) fn finish(self) {
+        if self.rx.is_empty() {
+            return;
+        }
+
+        self.capture.record(&CaptureRecord {
+            time: self.rx_time,
+            port: self.port,
+            framing: self.framing,
+            direction: Direction::Response,
+            data: self.rx,
+        });
+    }
+}
+
+impl Read for Recorder<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 && self.rx.is_empty() {
+            self.rx_time = current_timestamp();
+        }
+        self.rx.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl Write for Recorder<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.tx.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        if !self.tx.is_empty() {
+            let data = std::mem::take(&mut self.tx);
+            let record = CaptureRecord::new(&self.port, self.framing, Direction::Request, data);
+            self.capture.record(&record);
+        }
+        Ok(())
+    }
+}
+
+impl Transport for Recorder<'_> {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.inner.clear_input()
+    }
+}
+
+/// 按照记录回放响应
+///
+/// 每次写入请求后，取出记录中的下一条请求，其后的响应作为读取的数据；没有响应时读取超时。
+/// 请求与记录不一致时记录警告后照常回放。Modbus TCP 响应的事务号替换为写入请求的事务号。
+pub struct Replay {
+    records: VecDeque<CaptureRecord>,
+    tx: Vec<u8>,
+    rx: VecDeque<u8>,
+}
+
+impl Replay {
+    pub fn new<I: IntoIterator<Item = CaptureRecord>>(records: I) -> Self {
+        Self {
+            records: records.into_iter().collect(),
+            tx: Vec::new(),
+            rx: VecDeque::new(),
+        }
+    }
+
+    /// 读取记录文件，`port` 不为空时只回放该端口的记录
+    pub fn open<P: AsRef<Path>>(path: P, port: Option<&str>) -> Result<Self> {
+        let mut records = read_capture(path)?;
+        if let Some(port) = port {
+            records.retain(|r| r.port == port);
+        }
+        Ok(Self::new(records))
+    }
+
+    /// 尚未回放的请求数
+    pub fn remaining(&self) -> usize {
+        self.records
+            .iter()
+            .filter(|r| r.direction == Direction::Request)
+            .count()
+    }
+
+    /// 取出下一条请求之后的响应
+    fn respond(&mut self, request: &[u8]) {
+        // 第一条请求之前的数据是记录开始时残留的
+        while let Some(record) = self.records.pop_front() {
+            if record.direction != Direction::Request {
+                continue;
+            }
+
+            let tcp = record.framing == Framing::Tcp;
+            let same = match tcp {
+                // 事务号不同
+                true => record.data.get(2..) == request.get(2..),
+                false => record.data == request,
+            };
+            if !same {
+                log::warn!(
+                    "回放的请求与记录不一致: 记录 {:02X?}, 实际 {request:02X?}",
+                    record.data
+                );
+            }
+
+            while let Some(record) = self.records.front() {
+                if record.direction == Direction::Request {
+                    break;
+                }
+
+                let mut data = self.records.pop_front().unwrap().data;
+                if tcp && data.len() >= 2 && request.len() >= 2 {
+                    data[..2].copy_from_slice(&request[..2]);
+                }
+                self.rx.extend(data);
+            }
+            return;
+        }
+
+        log::warn!("记录已全部回放，请求 {request:02X?} 没有响应");
+    }
+}
+
+impl Read for Replay {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rx.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "无响应"));
+        }
+
+        let n = buf.len().min(self.rx.len());
+        for (b, v) in buf.iter_mut().zip(self.rx.drain(..n)) {
+            *b = v;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for Replay {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.tx.is_empty() {
+            let request = std::mem::take(&mut self.tx);
+            self.respond(&request);
+        }
+        Ok(())
+    }
+}
+
+impl Transport for Replay {
+    fn set_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.rx.clear();
+        Ok(())
+    }
+}
+
+/// 字节与 `01 03 00 0A` 形式的十六进制字符串互相转换
+mod hex {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex = data
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.split_whitespace()
+            .map(|b| u8::from_str_radix(b, 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
@@ -0,0 +1,594 @@
+//! 无 IO 的 Modbus 编解码
+//!
+//! 只处理字节：输入已收到的数据，输出完整的一帧或者“还需要更多数据”；将一帧编码为字节。
+//! 不依赖串口或任何传输，[`Builder`](crate::protocol::Builder)、模拟设备、抓包和测试共用这里的解析。
+//! 所有长度在取值前检查，任意输入只会返回错误，不会 panic。
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, error::Error};
+
+/// RTU 帧最大长度
+pub const RTU_MAX_LEN: usize = 256;
+
+/// ASCII 帧最大长度: `:` + 2 * (从站 + PDU + LRC) + CRLF
+pub const ASCII_MAX_LEN: usize = 513;
+
+/// MBAP 头长度
+pub const MBAP_LEN: usize = 7;
+
+/// PDU 最大长度
+const PDU_MAX_LEN: usize = 253;
+
+/// 帧格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Framing {
+    /// Modbus RTU: 从站 + PDU + CRC
+    #[default]
+    Rtu,
+    /// Modbus ASCII: `:` + 十六进制(从站 + PDU + LRC) + CRLF
+    Ascii,
+    /// RTU over TCP: 网关透传 RTU 帧，端口使用 `tcp://host:port`
+    RtuOverTcp,
+    /// Modbus TCP: MBAP 头 + PDU，无 CRC
+    Tcp,
+}
+
+impl Framing {
+    pub const ALL: [Framing; 4] = [
+        Framing::Rtu,
+        Framing::Ascii,
+        Framing::RtuOverTcp,
+        Framing::Tcp,
+    ];
+}
+
+impl fmt::Display for Framing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Framing::Rtu => "RTU",
+                Framing::Ascii => "ASCII",
+                Framing::RtuOverTcp => "RTU over TCP",
+                Framing::Tcp => "TCP",
+            }
+        )
+    }
+}
+
+/// 帧的方向，RTU 请求和响应的长度规则不同
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// 主站发出的请求
+    #[serde(rename = "tx")]
+    Request,
+    /// 从站返回的响应
+    #[serde(rename = "rx")]
+    Response,
+}
+
+/// 一帧数据: 从站 + PDU，事务号仅用于 Modbus TCP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub transaction: u16,
+    pub slave: u8,
+    pub pdu: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(transaction: u16, slave: u8, pdu: Vec<u8>) -> Self {
+        Self {
+            transaction,
+            slave,
+            pdu,
+        }
+    }
+
+    /// 按照帧格式编码
+    pub fn encode(&self, framing: Framing) -> Vec<u8> {
+        encode_frame(framing, self.transaction, self.slave, self.pdu.clone())
+    }
+}
+
+/// 解码结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decoded {
+    /// 完整的一帧，以及这一帧占用的字节数
+    Frame(Frame, usize),
+    /// 数据不足，`Some(n)` 时一帧至少需要 n 字节，`None` 时长度未知（RTU 由帧间静默判断结束）
+    Incomplete(Option<usize>),
+}
+
+/// 从缓存开头解码一帧
+///
+/// 长度足够时解析并校验这一帧，多余的数据属于下一帧
+pub fn decode(framing: Framing, direction: Direction, buffer: &[u8]) -> Result<Decoded> {
+    match frame_len(framing, direction, buffer)? {
+        Some(len) if buffer.len() >= len => {
+            let frame = decode_frame(framing, &buffer[..len])?;
+            Ok(Decoded::Frame(frame, len))
+        }
+        len => Ok(Decoded::Incomplete(len)),
+    }
+}
+
+/// 按照已收到的数据计算帧长度
+///
+/// 返回 `Some(n)` 时帧长度至少为 `n`，数据不足时随接收逐步确定；无法确定时返回 `None`
+pub fn frame_len(framing: Framing, direction: Direction, buffer: &[u8]) -> Result<Option<usize>> {
+    match framing {
+        Framing::Rtu | Framing::RtuOverTcp => Ok(match direction {
+            Direction::Request => rtu_request_len(buffer),
+            Direction::Response => rtu_response_len(buffer),
+        }),
+        Framing::Ascii => match buffer.iter().position(|&b| b == b'\n') {
+            Some(end) => Ok(Some(end + 1)),
+            None if buffer.len() >= ASCII_MAX_LEN => Err(Error::AsciiFormat),
+            None => Ok(None),
+        },
+        Framing::Tcp => match buffer.get(..MBAP_LEN) {
+            Some(header) => Ok(Some(MBAP_LEN + Mbap::parse(header)?.pdu_len())),
+            None => Ok(Some(MBAP_LEN)),
+        },
+    }
+}
+
+/// 按照已收到的数据计算 RTU 响应帧长度
+///
+/// 返回 `Some(n)` 时帧长度至少为 `n`，数据不足时随读取逐步确定；
+/// 无法确定长度的功能码返回 `None`，由帧间静默判断结束
+pub fn rtu_response_len(frame: &[u8]) -> Option<usize> {
+    let Some(&code) = frame.get(1) else {
+        return Some(2);
+    };
+
+    // 异常响应: 从站 + 功能码 + 异常码 + CRC
+    if code & 0x80 != 0 {
+        return Some(5);
+    }
+
+    match code {
+        // 从站 + 功能码 + 字节数 + 数据 + CRC
+        0x01..=0x04 | 0x17 => match frame.get(2) {
+            Some(&byte_count) => Some(5 + byte_count as usize),
+            None => Some(3),
+        },
+        // 回显 地址 + 值 / 地址 + 数量
+        0x05 | 0x06 | 0x08 | 0x0B | 0x0F | 0x10 => Some(8),
+        // 地址 + AND 掩码 + OR 掩码
+        0x16 => Some(10),
+        0x2B => device_id_response_len(frame),
+        _ => None,
+    }
+}
+
+/// 读取设备标识响应长度
+///
+/// 从站 + 2B + 0E + 读取码 + 一致性 + 后续 + 下一对象 + 对象数 + (对象 + 长度 + 值) * n + CRC
+fn device_id_response_len(frame: &[u8]) -> Option<usize> {
+    match frame.get(2) {
+        Some(0x0E) => {}
+        Some(_) => return None,
+        None => return Some(3),
+    }
+
+    let Some(&count) = frame.get(7) else {
+        return Some(8);
+    };
+
+    let mut len = 8;
+    for _ in 0..count {
+        let Some(&object_len) = frame.get(len + 1) else {
+            return Some(len + 2);
+        };
+        len += 2 + object_len as usize;
+    }
+
+    Some(len + 2)
+}
+
+/// 按照已收到的数据计算 RTU 请求帧长度
+///
+/// 多个寄存器写入的请求在本项目中为 地址 + 值，长度由帧间静默判断
+pub fn rtu_request_len(frame: &[u8]) -> Option<usize> {
+    let Some(&code) = frame.get(1) else {
+        return Some(2);
+    };
+
+    match code {
+        // 从站 + 功能码 + 地址 + 数量/值 + CRC
+        0x01..=0x06 | 0x08 => Some(8),
+        // 从站 + 功能码 + CRC
+        0x0B => Some(4),
+        // 从站 + 2B + 0E + 读取码 + 对象 + CRC
+        0x2B => Some(7),
+        // 地址 + 数量 + 字节数 + 按位打包的值 + CRC
+        0x0F => match frame.get(6) {
+            Some(&byte_count) => Some(9 + byte_count as usize),
+            None => Some(7),
+        },
+        // 地址 + AND 掩码 + OR 掩码
+        0x16 => Some(10),
+        // 读地址 + 读数量 + 写地址 + 写数量 + 字节数 + 值 + CRC
+        0x17 => match frame.get(10) {
+            Some(&byte_count) => Some(13 + byte_count as usize),
+            None => Some(11),
+        },
+        _ => None,
+    }
+}
+
+/// 将整个缓存作为一帧解析，校验 CRC/LRC 或 MBAP 长度
+pub fn decode_frame(framing: Framing, frame: &[u8]) -> Result<Frame> {
+    let (transaction, slave, pdu) = match framing {
+        Framing::Rtu | Framing::RtuOverTcp => {
+            if frame.len() < 4 {
+                return Err(Error::DataShort(frame.len()));
+            }
+            if frame.len() > RTU_MAX_LEN {
+                return Err(Error::DataLenError);
+            }
+            check_crc(frame)?;
+            (0, frame[0], frame[1..frame.len() - 2].to_vec())
+        }
+        Framing::Ascii => {
+            let body = ascii_decode(frame)?;
+            let Some((&slave, pdu)) = body.split_first() else {
+                return Err(Error::DataShort(0));
+            };
+            (0, slave, pdu.to_vec())
+        }
+        Framing::Tcp => {
+            let header = Mbap::parse(frame)?;
+            let pdu = &frame[MBAP_LEN..];
+            if pdu.len() != header.pdu_len() {
+                return Err(Error::DataLenError);
+            }
+            (header.transaction, header.unit, pdu.to_vec())
+        }
+    };
+
+    if pdu.is_empty() {
+        return Err(Error::DataShort(0));
+    }
+
+    Ok(Frame::new(transaction, slave, pdu))
+}
+
+/// 按照帧格式拆分出 从站 + PDU，同时校验 CRC/LRC
+pub fn split_frame(framing: Framing, frame: &[u8]) -> Result<(u8, Vec<u8>)> {
+    let frame = decode_frame(framing, frame)?;
+    Ok((frame.slave, frame.pdu))
+}
+
+/// 按照帧格式将 从站 + PDU 编码为一帧，事务号仅用于 Modbus TCP
+pub fn encode_frame(framing: Framing, transaction: u16, slave: u8, pdu: Vec<u8>) -> Vec<u8> {
+    match framing {
+        Framing::Rtu | Framing::RtuOverTcp => rtu_frame(slave, pdu),
+        Framing::Ascii => ascii_frame(slave, pdu),
+        Framing::Tcp => tcp_frame(transaction, slave, pdu),
+    }
+}
+
+/// 流式解码器
+///
+/// 处理一帧分多次到达以及一次收到多帧的情况，出错时丢弃出错的一帧，之后的数据继续解析
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    framing: Framing,
+    direction: Direction,
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new(framing: Framing, direction: Direction) -> Self {
+        Self {
+            framing,
+            direction,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// 追加收到的数据
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// 缓存中尚未解析的数据
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// 取出下一帧，数据不足时返回 `Ok(None)`
+    pub fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let len = match frame_len(self.framing, self.direction, &self.buffer) {
+            Ok(len) => len,
+            Err(e) => {
+                self.buffer.clear();
+                return Err(e);
+            }
+        };
+
+        match len {
+            Some(len) if self.buffer.len() >= len => {
+                let frame: Vec<u8> = self.buffer.drain(..len).collect();
+                decode_frame(self.framing, &frame).map(Some)
+            }
+            _ if self.buffer.len() > ASCII_MAX_LEN => {
+                self.buffer.clear();
+                Err(Error::DataLenError)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// 帧已结束（RTU 帧间静默或连接关闭），将缓存中剩余的数据作为一帧解析
+    pub fn finish(&mut self) -> Result<Option<Frame>> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let frame = std::mem::take(&mut self.buffer);
+        decode_frame(self.framing, &frame).map(Some)
+    }
+}
+
+/// Modbus TCP MBAP 头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mbap {
+    /// 事务号
+    pub transaction: u16,
+    /// 协议标识，Modbus 为 0
+    pub protocol: u16,
+    /// 后续字节数（单元标识 + PDU）
+    pub length: u16,
+    /// 单元标识（从站）
+    pub unit: u8,
+}
+
+impl Mbap {
+    pub fn new(transaction: u16, unit: u8, pdu_len: usize) -> Self {
+        Self {
+            transaction,
+            protocol: 0,
+            length: pdu_len as u16 + 1,
+            unit,
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < MBAP_LEN {
+            return Err(Error::DataShort(data.len()));
+        }
+
+        let header = Self {
+            transaction: u16::from_be_bytes([data[0], data[1]]),
+            protocol: u16::from_be_bytes([data[2], data[3]]),
+            length: u16::from_be_bytes([data[4], data[5]]),
+            unit: data[6],
+        };
+
+        if header.protocol != 0 {
+            return Err(Error::ProtocolId(header.protocol));
+        }
+
+        if header.length < 2 || header.pdu_len() > PDU_MAX_LEN {
+            return Err(Error::DataLenError);
+        }
+
+        Ok(header)
+    }
+
+    /// PDU 长度
+    pub fn pdu_len(&self) -> usize {
+        self.length.saturating_sub(1) as usize
+    }
+
+    pub fn to_bytes(&self) -> [u8; MBAP_LEN] {
+        let [t0, t1] = self.transaction.to_be_bytes();
+        let [p0, p1] = self.protocol.to_be_bytes();
+        let [l0, l1] = self.length.to_be_bytes();
+        [t0, t1, p0, p1, l0, l1, self.unit]
+    }
+}
+
+/// 校验 RTU 帧末尾的 CRC（低位在前）
+fn check_crc(frame: &[u8]) -> Result<()> {
+    let len = frame.len();
+    if len < 3 {
+        return Err(Error::DataShort(len));
+    }
+
+    let expected = calculate_crc(&frame[..len - 2]);
+    let actual = u16::from_le_bytes([frame[len - 2], frame[len - 1]]);
+    if expected != actual {
+        return Err(Error::Crc(expected, actual));
+    }
+
+    Ok(())
+}
+
+fn rtu_frame(slave: u8, pdu: Vec<u8>) -> Vec<u8> {
+    let mut frame = vec![slave];
+    frame.extend(pdu);
+    let crc = calculate_crc(&frame);
+    frame.extend(crc.to_le_bytes());
+    frame
+}
+
+fn tcp_frame(transaction: u16, unit: u8, pdu: Vec<u8>) -> Vec<u8> {
+    let mut frame = Mbap::new(transaction, unit, pdu.len()).to_bytes().to_vec();
+    frame.extend(pdu);
+    frame
+}
+
+fn ascii_frame(slave: u8, pdu: Vec<u8>) -> Vec<u8> {
+    let mut body = vec![slave];
+    body.extend(pdu);
+    body.push(calculate_lrc(&body));
+
+    let mut frame = vec![b':'];
+    for b in body {
+        frame.extend_from_slice(format!("{b:02X}").as_bytes());
+    }
+    frame.extend_from_slice(b"\r\n");
+    frame
+}
+
+/// 解析 ASCII 帧，校验 LRC 后返回 从站 + PDU
+fn ascii_decode(frame: &[u8]) -> Result<Vec<u8>> {
+    let hex = frame
+        .strip_prefix(b":")
+        .and_then(|f| f.strip_suffix(b"\r\n"))
+        .ok_or(Error::AsciiFormat)?;
+
+    if !hex.len().is_multiple_of(2) {
+        return Err(Error::AsciiFormat);
+    }
+
+    // from_str_radix 接受 `+` 前缀，先确认都是十六进制字符
+    if !hex.iter().all(u8::is_ascii_hexdigit) {
+        return Err(Error::AsciiFormat);
+    }
+
+    let mut body = hex
+        .chunks_exact(2)
+        .map(|h| {
+            std::str::from_utf8(h)
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or(Error::AsciiFormat)
+        })
+        .collect::<std::result::Result<Vec<u8>, Error>>()?;
+
+    let lrc = body.pop().ok_or(Error::DataShort(0))?;
+    let expected = calculate_lrc(&body);
+    if lrc != expected {
+        return Err(Error::Lrc(expected, lrc));
+    }
+
+    Ok(body)
+}
+
+/// 计算 Modbus RTU CRC 校验码
+pub fn calculate_crc(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            if crc & 0x0001 != 0 {
+                crc >>= 1;
+                crc ^= 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+
+    crc
+}
+
+/// 计算 Modbus ASCII LRC 校验码
+pub fn calculate_lrc(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |lrc, &b| lrc.wrapping_add(b))
+        .wrapping_neg()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::{Function, FunctionCode};
+
+    #[test]
+    fn codec_stream() {
+        let request = Function::new(0x05, FunctionCode::ReadInputRegisters, vec![0, 30]);
+        let request = request.request_data();
+        let data = (0..30).collect();
+        let response = Function::new(0x05, FunctionCode::ReadInputRegisters, data).response_data();
+
+        // 响应分多次到达，并且后面紧跟下一帧
+        let mut decoder = Decoder::new(Framing::Rtu, Direction::Response);
+        let mut stream = response.clone();
+        stream.extend_from_slice(&response[..3]);
+        for chunk in stream.chunks(7) {
+            decoder.push(chunk);
+        }
+
+        let frame = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(0x05, frame.slave);
+        assert_eq!(response, frame.encode(Framing::Rtu));
+        assert!(decoder.next_frame().unwrap().is_none());
+        assert_eq!(&response[..3], decoder.buffered());
+
+        // 请求方向按照功能码确定长度
+        assert_eq!(
+            decode(Framing::Rtu, Direction::Request, &request[..5]).unwrap(),
+            Decoded::Incomplete(Some(8))
+        );
+
+        for framing in Framing::ALL {
+            let frame = Frame::new(7, 0x05, vec![0x04, 0x02, 0x12, 0x34]);
+            let bytes = frame.encode(framing);
+            match decode(framing, Direction::Response, &bytes).unwrap() {
+                Decoded::Frame(decoded, len) => {
+                    assert_eq!(bytes.len(), len);
+                    assert_eq!(frame.pdu, decoded.pdu);
+                }
+                Decoded::Incomplete(_) => panic!("{framing} 未能解码"),
+            }
+        }
+    }
+
+    #[test]
+    fn codec_crafted_input() {
+        // 任意输入只返回错误，不会 panic
+        let mut seed: u32 = 0x1234_5678;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as u8
+        };
+
+        for len in 0..300 {
+            let mut data: Vec<u8> = (0..len % 64).map(|_| next()).collect();
+            if len % 3 == 0 {
+                data.insert(0, b':');
+            }
+
+            for framing in Framing::ALL {
+                for direction in [Direction::Request, Direction::Response] {
+                    let _ = decode(framing, direction, &data);
+                    let mut decoder = Decoder::new(framing, direction);
+                    decoder.push(&data);
+                    while let Ok(Some(_)) = decoder.next_frame() {}
+                    let _ = decoder.finish();
+                }
+                let _ = Function::parse_request_frame(framing, &data);
+                let _ = Function::parse_response_frame(framing, &data);
+            }
+        }
+
+        // `+` 前缀不是十六进制字符
+        let frame = Frame::new(0, 0x01, vec![0x03, 0x02, 0x00, 0x0F]).encode(Framing::Ascii);
+        assert!(ascii_decode(&frame).is_ok());
+        let mut crafted = frame.clone();
+        crafted[1] = b'+';
+        assert!(matches!(ascii_decode(&crafted), Err(Error::AsciiFormat)));
+        assert!(Function::parse_response_ascii(&crafted).is_err());
+
+        // 字节数超过实际长度
+        let mut frame = vec![0x05, 0x04, 0xFF, 0x00];
+        let crc = calculate_crc(&frame);
+        frame.extend(crc.to_le_bytes());
+        assert!(Function::parse_response(&frame).is_err());
+    }
+}
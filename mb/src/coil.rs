@@ -0,0 +1,147 @@
+//! 线圈与离散输入
+//!
+//! 继电器板、DI 模块等按位读写的设备，数据按位打包，第一个线圈为第一个字节的最低位。
+//! 读取线圈 0-9 的请求与响应（线圈 0、2、9 为开）
+//! ```
+//! let req = vec![0x01, 0x01, 0x00, 0x00, 0x00, 0x0A, 0xBC, 0x0D];
+//! let res = vec![0x01, 0x01, 0x02, 0x05, 0x02, 0x3B, 0x6D];
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    device::Device,
+    error::Error,
+    protocol::{FunRequest, FunResponse, Function, FunctionCode, pack_bits},
+    utils::current_timestamp,
+};
+
+/// 线圈写入 ON 的值
+const COIL_ON: u16 = 0xFF00;
+
+pub struct Coil;
+
+impl Device for Coil {
+    type Command = CoilMode;
+    type Output = CoilData;
+
+    fn request(slave: u8, mode: &CoilMode) -> FunRequest {
+        let mode = mode.params();
+        Function::with_bytes(slave, mode.0, mode.1)
+    }
+
+    fn decode(mode: &CoilMode, response: FunResponse) -> crate::Result<CoilData> {
+        CoilData::from_response(mode, response)
+    }
+}
+
+/// 命令请求类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoilMode {
+    /// 读取线圈: 起始地址, 数量
+    Read(u16, u16),
+    /// 读取离散输入: 起始地址, 数量
+    ReadInputs(u16, u16),
+    /// 写入单个线圈: 地址, 开关
+    Write(u16, bool),
+    /// 写入多个线圈: 起始地址, 开关
+    WriteMultiple(u16, Vec<bool>),
+}
+
+impl CoilMode {
+    /// 获取参数 (功能, 数据)
+    pub fn params(&self) -> (FunctionCode, Vec<u8>) {
+        match self {
+            CoilMode::Read(address, quantity) => (
+                FunctionCode::ReadCoils,
+                words(&[*address, (*quantity).clamp(1, 2000)]),
+            ),
+            CoilMode::ReadInputs(address, quantity) => (
+                FunctionCode::ReadDiscreteInputs,
+                words(&[*address, (*quantity).clamp(1, 2000)]),
+            ),
+            CoilMode::Write(address, on) => {
+                let value = if *on { COIL_ON } else { 0 };
+                (FunctionCode::WriteSingleCoil, words(&[*address, value]))
+            }
+            CoilMode::WriteMultiple(address, values) => {
+                let values = &values[..values.len().min(1968)];
+                let bytes = pack_bits(values);
+
+                let mut data = words(&[*address, values.len() as u16]);
+                data.push(bytes.len() as u8);
+                data.extend(bytes);
+                (FunctionCode::WriteMultipleCoils, data)
+            }
+        }
+    }
+
+    /// 读取的数量，写入命令为 0
+    pub fn quantity(&self) -> usize {
+        match self {
+            CoilMode::Read(_, quantity) | CoilMode::ReadInputs(_, quantity) => *quantity as usize,
+            _ => 0,
+        }
+    }
+}
+
+fn words(values: &[u16]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_be_bytes()).collect()
+}
+
+/// 线圈状态
+///
+/// 读取响应按字节补齐，`values` 的长度为 8 的倍数，使用 [`CoilData::truncate`] 截取请求的数量
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoilData {
+    pub time: Duration,
+    pub slave: u8,
+    pub values: Vec<bool>,
+}
+
+impl CoilData {
+    /// 第 `index` 个线圈是否为开
+    pub fn get(&self, index: usize) -> Option<bool> {
+        self.values.get(index).copied()
+    }
+
+    /// 只保留请求的数量
+    pub fn truncate(mut self, quantity: usize) -> Self {
+        self.values.truncate(quantity);
+        self
+    }
+
+    /// 按照请求解析响应，读取时截取请求的数量
+    pub fn from_response(mode: &CoilMode, response: FunResponse) -> crate::Result<Self> {
+        let data = CoilData::try_from(response)?;
+        Ok(match mode {
+            CoilMode::Read(..) | CoilMode::ReadInputs(..) => data.truncate(mode.quantity()),
+            _ => data,
+        })
+    }
+}
+
+impl TryFrom<FunResponse> for CoilData {
+    type Error = crate::error::Error;
+
+    fn try_from(value: FunResponse) -> std::result::Result<Self, Self::Error> {
+        let data = value.data_u8();
+        let values = match value.code() {
+            FunctionCode::ReadCoils | FunctionCode::ReadDiscreteInputs => value.data_bits(),
+            // 回显 地址 + 值
+            FunctionCode::WriteSingleCoil => {
+                let state = data.get(2..4).ok_or(Error::DataNull)?;
+                vec![u16::from_be_bytes([state[0], state[1]]) == COIL_ON]
+            }
+            // 确认 地址 + 数量，不含状态
+            FunctionCode::WriteMultipleCoils => Vec::new(),
+            _ => return Err(Error::DataNull),
+        };
+
+        Ok(CoilData {
+            time: current_timestamp(),
+            slave: value.slave(),
+            values,
+        })
+    }
+}
@@ -0,0 +1,311 @@
+//! 连接管理
+//!
+//! 每个 [`Endpoint`]（串口为 (端口, 波特率)）只保持一个打开的句柄，跨线程通过 `Mutex` 串行访问。
+//! 读写出现 IO 错误时关闭句柄并重新打开，空闲超过 [`IDLE_TIMEOUT`] 的端口由后台线程关闭。
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Result,
+    error::Error,
+    transport::{Endpoint, Transport},
+};
+
+/// 读取超时
+pub const READ_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// 空闲超时，超过后关闭端口
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 空闲检查间隔
+const REAPER_INTERVAL: Duration = Duration::from_secs(5);
+
+pub type SharedConnection = Arc<Mutex<Connection>>;
+
+type Opener = Box<dyn FnMut() -> Result<Box<dyn Transport>> + Send>;
+
+/// 单个连接
+pub struct Connection {
+    endpoint: Endpoint,
+    transport: Option<Box<dyn Transport>>,
+    /// 自定义的打开方式，为空时按照 [`Endpoint::open`] 打开
+    opener: Option<Opener>,
+    last_used: Instant,
+}
+
+impl Connection {
+    fn new(endpoint: Endpoint) -> Self {
+        Self {
+            endpoint,
+            transport: None,
+            opener: None,
+            last_used: Instant::now(),
+        }
+    }
+
+    /// 使用已打开的传输创建连接，不进入连接池，断开后无法重连
+    pub fn with_transport<T: Into<String>>(name: T, transport: Box<dyn Transport>) -> Self {
+        Self {
+            endpoint: Endpoint::Custom { name: name.into() },
+            transport: Some(transport),
+            opener: None,
+            last_used: Instant::now(),
+        }
+    }
+
+    /// 使用自定义的打开方式创建连接，断开后调用 `opener` 重新打开
+    pub fn with_opener<F>(endpoint: Endpoint, opener: F) -> Self
+    where
+        F: FnMut() -> Result<Box<dyn Transport>> + Send + 'static,
+    {
+        Self {
+            endpoint,
+            transport: None,
+            opener: Some(Box::new(opener)),
+            last_used: Instant::now(),
+        }
+    }
+
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.transport.is_some()
+    }
+
+    /// 关闭句柄，下次使用时重新打开
+    pub fn close(&mut self) {
+        if self.transport.take().is_some() {
+            log::debug!("关闭连接: {}", self.endpoint);
+        }
+    }
+
+    fn open(&mut self) -> Result<&mut Box<dyn Transport>> {
+        if self.transport.is_none() {
+            log::debug!("打开连接: {}", self.endpoint);
+            let transport = match &mut self.opener {
+                Some(opener) => opener()?,
+                None => self.endpoint.open(READ_TIMEOUT)?,
+            };
+            self.transport = Some(transport);
+        }
+
+        Ok(self.transport.as_mut().unwrap())
+    }
+
+    /// 距离上次收发不足 `delay` 时等待
+    pub fn wait(&self, delay: Duration) {
+        let elapsed = self.last_used.elapsed();
+        if elapsed < delay {
+            thread::sleep(delay - elapsed);
+        }
+    }
+
+    /// 在传输上执行一次收发，IO 错误时重连并重试一次
+    pub fn exchange<R, F>(&mut self, mut f: F) -> Result<R>
+    where
+        F: FnMut(&mut dyn Transport) -> Result<R>,
+    {
+        self.last_used = Instant::now();
+
+        let result = self.open().and_then(|t| f(t.as_mut()));
+        let result = match result {
+            Err(e) if is_reconnect_error(&e) => {
+                log::warn!("{} 读写失败，重新连接: {e}", self.endpoint);
+                self.close();
+
+                let result = self.open().and_then(|t| f(t.as_mut()));
+                if result.is_err() {
+                    self.close();
+                }
+                result
+            }
+            result => result,
+        };
+        self.last_used = Instant::now();
+        result
+    }
+}
+
+/// 传输错误（超时除外）需要重新连接
+pub(crate) fn is_reconnect_error(e: &Error) -> bool {
+    e.is_port_gone()
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("endpoint", &self.endpoint)
+            .field("open", &self.is_open())
+            .finish()
+    }
+}
+
+fn pool() -> &'static Mutex<HashMap<Endpoint, SharedConnection>> {
+    static POOL: OnceLock<Mutex<HashMap<Endpoint, SharedConnection>>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        thread::Builder::new()
+            .name("mb-connection-reaper".into())
+            .spawn(|| {
+                loop {
+                    thread::sleep(REAPER_INTERVAL);
+                    close_idle(IDLE_TIMEOUT);
+                }
+            })
+            .ok();
+
+        Mutex::new(HashMap::new())
+    })
+}
+
+pub(crate) fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 获取共享连接，不存在时创建（端口在第一次读写时打开）
+pub fn get_connection(endpoint: &Endpoint) -> SharedConnection {
+    let mut pool = lock(pool());
+    pool.entry(endpoint.clone())
+        .or_insert_with(|| Arc::new(Mutex::new(Connection::new(endpoint.clone()))))
+        .clone()
+}
+
+/// 关闭空闲超过 `idle` 的端口，正在使用的连接跳过
+pub fn close_idle(idle: Duration) {
+    let pool = lock(pool());
+    for conn in pool.values() {
+        if let Ok(mut conn) = conn.try_lock()
+            && conn.is_open()
+            && conn.last_used.elapsed() >= idle
+        {
+            conn.close();
+        }
+    }
+}
+
+/// 关闭全部端口
+pub fn close_all() {
+    let pool = lock(pool());
+    for conn in pool.values() {
+        lock(conn).close();
+    }
+}
+
+/// 关闭指定串口的全部连接
+pub fn close_port(port_name: &str) {
+    let pool = lock(pool());
+    pool.iter()
+        .filter(|(endpoint, _)| {
+            matches!(endpoint, Endpoint::Serial { port_name: name, .. } if name == port_name)
+        })
+        .for_each(|(_, conn)| lock(conn).close());
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{self, Read, Write},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    /// 回显一个字节，`broken` 时写入失败
+    struct Port {
+        broken: bool,
+    }
+
+    impl Read for Port {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            buf[0] = 0x01;
+            Ok(1)
+        }
+    }
+
+    impl Write for Port {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.broken {
+                true => Err(io::Error::new(io::ErrorKind::BrokenPipe, "端口已断开")),
+                false => Ok(buf.len()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transport for Port {
+        fn set_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// 记录打开次数，第一次打开的句柄已断开
+    fn counted(name: &str) -> (Connection, Arc<AtomicUsize>) {
+        let opens = Arc::new(AtomicUsize::new(0));
+        let counter = opens.clone();
+        let endpoint = Endpoint::Custom { name: name.into() };
+        let conn = Connection::with_opener(endpoint, move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(Port { broken: n == 0 }) as Box<dyn Transport>)
+        });
+        (conn, opens)
+    }
+
+    fn echo(conn: &mut Connection) -> Result<u8> {
+        conn.exchange(|port| {
+            port.write_all(&[0x01])?;
+            let mut buf = [0u8];
+            port.read_exact(&mut buf)?;
+            Ok(buf[0])
+        })
+    }
+
+    #[test]
+    fn reuse_per_port_and_baudrate() {
+        let a = get_connection(&Endpoint::serial("/dev/ttyTEST0", 9600));
+        let b = get_connection(&Endpoint::serial("/dev/ttyTEST0", 9600));
+        let c = get_connection(&Endpoint::serial("/dev/ttyTEST0", 19200));
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        // 第一次读写时才打开
+        assert!(!lock(&a).is_open());
+    }
+
+    #[test]
+    fn reconnect_after_io_error() {
+        let (mut conn, opens) = counted("reconnect");
+        assert_eq!(0x01, echo(&mut conn).unwrap());
+        assert_eq!(2, opens.load(Ordering::SeqCst));
+
+        // 重连后的句柄继续使用
+        assert_eq!(0x01, echo(&mut conn).unwrap());
+        assert_eq!(2, opens.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn close_idle_connection() {
+        let (conn, opens) = counted("idle");
+        let conn = Arc::new(Mutex::new(conn));
+        lock(pool()).insert(lock(&conn).endpoint.clone(), conn.clone());
+
+        echo(&mut lock(&conn)).unwrap();
+        close_idle(Duration::from_secs(60));
+        assert!(lock(&conn).is_open());
+
+        thread::sleep(Duration::from_millis(20));
+        close_idle(Duration::from_millis(10));
+        assert!(!lock(&conn).is_open());
+
+        echo(&mut lock(&conn)).unwrap();
+        assert_eq!(3, opens.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,43 @@
+//! 设备驱动
+//!
+//! 每种设备把命令类型、请求帧和响应数据绑定在一起，由 [`Builder::execute`](crate::protocol::Builder::execute)
+//! 统一发送和解析，新增设备只需要实现 [`Device`]。
+//! ```no_run
+//! use mb::{device::Device, protocol::Builder, temperature::{Temperature, TemperatureMode}};
+//!
+//! let builder = Builder::new("COM1", 9600);
+//! let data = builder.execute::<Temperature>(0x01, &TemperatureMode::Temp1).unwrap();
+//! let request = Temperature::request(0x01, &TemperatureMode::Temp1);
+//! ```
+
+use crate::{
+    Result,
+    error::Error,
+    protocol::{FunRequest, FunResponse},
+};
+
+pub trait Device {
+    /// 命令类型
+    type Command;
+    /// 响应数据
+    type Output;
+
+    /// 生成请求
+    fn request(slave: u8, command: &Self::Command) -> FunRequest;
+
+    /// 按照命令解析响应
+    fn decode(command: &Self::Command, response: FunResponse) -> Result<Self::Output>;
+
+    /// 命令需要的全部请求，按顺序发送，默认只有 [`Device::request`]
+    ///
+    /// 跳过未映射寄存器的读取拆分为多个请求
+    fn requests(slave: u8, command: &Self::Command) -> Vec<FunRequest> {
+        vec![Self::request(slave, command)]
+    }
+
+    /// 按照命令解析 [`Device::requests`] 的全部响应，默认只解析第一个
+    fn decode_all(command: &Self::Command, responses: Vec<FunResponse>) -> Result<Self::Output> {
+        let response = responses.into_iter().next().ok_or(Error::DataNull)?;
+        Self::decode(command, response)
+    }
+}
@@ -0,0 +1,259 @@
+//! 诊断与设备标识
+//!
+//! 串口诊断 (0x08)、通信事件计数器 (0x0B) 以及读取设备标识 (0x2B/0x0E)，用于排查总线问题和识别设备型号。
+//! 读取从站 5 的基本设备标识
+//! ```
+//! let req = vec![0x05, 0x2B, 0x0E, 0x01, 0x00, 0x81, 0xB7];
+//! ```
+
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    device::Device,
+    error::Error,
+    protocol::{FunRequest, FunResponse, Function, FunctionCode},
+    utils::current_timestamp,
+};
+
+/// 读取设备标识的 MEI 类型
+const MEI_DEVICE_ID: u8 = 0x0E;
+
+/// 通信事件计数器状态字，从站忙
+const STATUS_BUSY: u16 = 0xFFFF;
+
+pub struct Diagnostics;
+
+impl Device for Diagnostics {
+    type Command = DiagnosticsMode;
+    type Output = DiagnosticsReply;
+
+    fn request(slave: u8, mode: &DiagnosticsMode) -> FunRequest {
+        let mode = mode.params();
+        Function::with_bytes(slave, mode.0, mode.1)
+    }
+
+    fn decode(mode: &DiagnosticsMode, response: FunResponse) -> crate::Result<DiagnosticsReply> {
+        Ok(match mode {
+            DiagnosticsMode::CommEventCounter => {
+                DiagnosticsReply::CommEventCounter(response.try_into()?)
+            }
+            DiagnosticsMode::DeviceId(..) => DiagnosticsReply::DeviceInfo(response.try_into()?),
+            _ => DiagnosticsReply::Diagnostics(response.try_into()?),
+        })
+    }
+}
+
+/// 按照命令区分的响应
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticsReply {
+    Diagnostics(DiagnosticsData),
+    CommEventCounter(CommEventCounter),
+    DeviceInfo(DeviceInfo),
+}
+
+/// 设备标识读取码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceIdCode {
+    /// 基本: 厂商、产品代码、版本
+    Basic = 0x01,
+    /// 常规: 基本 + 网址、产品名称、型号等
+    Regular = 0x02,
+    /// 扩展: 常规 + 厂商私有对象
+    Extended = 0x03,
+    /// 单个对象
+    Specific = 0x04,
+}
+
+/// 命令请求类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticsMode {
+    /// 回显请求数据
+    Echo(u16),
+    /// 重启通信，是否清空事件记录
+    RestartComm(bool),
+    /// 清空计数器和诊断寄存器
+    ClearCounters,
+    /// 总线报文计数
+    BusMessageCount,
+    /// 总线 CRC 错误计数
+    BusErrorCount,
+    /// 异常响应计数
+    ExceptionCount,
+    /// 本从站报文计数
+    SlaveMessageCount,
+    /// 本从站无响应计数
+    SlaveNoResponseCount,
+    /// 通信事件计数器 0x0B
+    CommEventCounter,
+    /// 读取设备标识: 读取码, 起始对象
+    DeviceId(DeviceIdCode, u8),
+}
+
+impl DiagnosticsMode {
+    /// 获取参数 (功能, 数据)
+    pub fn params(&self) -> (FunctionCode, Vec<u8>) {
+        let diagnostics = |sub_function: u16, value: u16| {
+            let mut data = sub_function.to_be_bytes().to_vec();
+            data.extend(value.to_be_bytes());
+            (FunctionCode::Diagnostics, data)
+        };
+
+        match self {
+            DiagnosticsMode::Echo(value) => diagnostics(0x00, *value),
+            DiagnosticsMode::RestartComm(clear) => {
+                diagnostics(0x01, if *clear { 0xFF00 } else { 0 })
+            }
+            DiagnosticsMode::ClearCounters => diagnostics(0x0A, 0),
+            DiagnosticsMode::BusMessageCount => diagnostics(0x0B, 0),
+            DiagnosticsMode::BusErrorCount => diagnostics(0x0C, 0),
+            DiagnosticsMode::ExceptionCount => diagnostics(0x0D, 0),
+            DiagnosticsMode::SlaveMessageCount => diagnostics(0x0E, 0),
+            DiagnosticsMode::SlaveNoResponseCount => diagnostics(0x0F, 0),
+            DiagnosticsMode::CommEventCounter => (FunctionCode::GetCommEventCounter, Vec::new()),
+            DiagnosticsMode::DeviceId(code, object) => (
+                FunctionCode::ReadDeviceIdentification,
+                vec![MEI_DEVICE_ID, *code as u8, *object],
+            ),
+        }
+    }
+}
+
+/// 诊断响应: 子功能 + 数据，计数类子功能的数据为计数值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticsData {
+    pub time: Duration,
+    pub slave: u8,
+    pub sub_function: u16,
+    pub value: u16,
+}
+
+impl TryFrom<FunResponse> for DiagnosticsData {
+    type Error = crate::error::Error;
+
+    fn try_from(value: FunResponse) -> std::result::Result<Self, Self::Error> {
+        if value.code() != FunctionCode::Diagnostics {
+            return Err(Error::DataNull);
+        }
+        let data = value.data();
+        let [sub_function, data, ..] = data[..] else {
+            return Err(Error::DataShort(data.len()));
+        };
+
+        Ok(DiagnosticsData {
+            time: current_timestamp(),
+            slave: value.slave(),
+            sub_function,
+            value: data,
+        })
+    }
+}
+
+/// 通信事件计数器
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommEventCounter {
+    pub time: Duration,
+    pub slave: u8,
+    /// 从站正在处理上一条命令
+    pub busy: bool,
+    /// 成功完成的报文数量
+    pub count: u16,
+}
+
+impl TryFrom<FunResponse> for CommEventCounter {
+    type Error = crate::error::Error;
+
+    fn try_from(value: FunResponse) -> std::result::Result<Self, Self::Error> {
+        if value.code() != FunctionCode::GetCommEventCounter {
+            return Err(Error::DataNull);
+        }
+        let data = value.data();
+        let [status, count, ..] = data[..] else {
+            return Err(Error::DataShort(data.len()));
+        };
+
+        Ok(CommEventCounter {
+            time: current_timestamp(),
+            slave: value.slave(),
+            busy: status == STATUS_BUSY,
+            count,
+        })
+    }
+}
+
+/// 设备标识
+///
+/// 对象 0x00 厂商、0x01 产品代码、0x02 版本为必需对象，其余为可选
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub time: Duration,
+    pub slave: u8,
+    pub objects: BTreeMap<u8, String>,
+    /// 一次响应放不下，需要从 `next_object` 继续读取
+    pub more_follows: bool,
+    pub next_object: u8,
+}
+
+impl DeviceInfo {
+    /// 厂商名称
+    pub fn vendor(&self) -> Option<&str> {
+        self.object(0x00)
+    }
+
+    /// 产品代码
+    pub fn product_code(&self) -> Option<&str> {
+        self.object(0x01)
+    }
+
+    /// 固件版本
+    pub fn revision(&self) -> Option<&str> {
+        self.object(0x02)
+    }
+
+    /// 型号，没有型号对象时使用产品代码
+    pub fn model(&self) -> Option<&str> {
+        self.object(0x05).or_else(|| self.product_code())
+    }
+
+    pub fn object(&self, id: u8) -> Option<&str> {
+        self.objects.get(&id).map(String::as_str)
+    }
+}
+
+impl TryFrom<FunResponse> for DeviceInfo {
+    type Error = crate::error::Error;
+
+    /// 0E + 读取码 + 一致性 + 后续 + 下一对象 + 对象数 + (对象 + 长度 + 值) * n
+    fn try_from(value: FunResponse) -> std::result::Result<Self, Self::Error> {
+        if value.code() != FunctionCode::ReadDeviceIdentification {
+            return Err(Error::DataNull);
+        }
+        let data = value.data_u8();
+        let header = data.get(..6).ok_or(Error::DataShort(data.len()))?;
+        if header[0] != MEI_DEVICE_ID {
+            return Err(Error::MbParseFail);
+        }
+
+        let mut objects = BTreeMap::new();
+        let mut rest = &data[6..];
+        for _ in 0..header[5] {
+            let [id, len, tail @ ..] = rest else {
+                return Err(Error::DataShort(data.len()));
+            };
+            let object = tail
+                .get(..*len as usize)
+                .ok_or(Error::DataShort(data.len()))?;
+            objects.insert(*id, String::from_utf8_lossy(object).trim().to_string());
+            rest = &tail[*len as usize..];
+        }
+
+        Ok(DeviceInfo {
+            time: current_timestamp(),
+            slave: value.slave(),
+            objects,
+            more_follows: header[3] == 0xFF,
+            next_object: header[4],
+        })
+    }
+}
@@ -1,5 +1,37 @@
+use std::io;
+
+use crate::protocol::{ExceptionCode, FunctionCode};
+
+/// 错误分类，调用方据此决定重试、标记离线还是提示用户
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// 端口打开失败或读写出错（超时除外）
+    Transport,
+    /// 设备在超时时间内没有响应
+    Timeout,
+    /// 帧格式、长度、地址或功能码不正确
+    Framing,
+    /// CRC/LRC 校验失败
+    Crc,
+    /// 从站返回异常响应
+    Exception,
+    /// 响应数据无法转换
+    Decode,
+    /// 请求参数或寄存器映射不正确，请求没有发出
+    Invalid,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error("端口读写失败: {0}")]
+    Io(io::Error),
+
+    #[error("串口错误: {0}")]
+    Serial(#[from] serialport::Error),
+
+    #[error("读取超时")]
+    Timeout,
+
     #[error("格式解析失败")]
     MbParseFail,
 
@@ -11,10 +43,117 @@ pub enum Error {
 
     #[error("数据为空")]
     DataNull,
+
+    #[error("寄存器 {address:#06X} 的值无效: {value}")]
+    InvalidValue { address: u16, value: u16 },
+
+    #[error("MBAP 协议标识错误: {0}")]
+    ProtocolId(u16),
+
+    #[error("ASCII 帧格式错误")]
+    AsciiFormat,
+
+    #[error("LRC 校验失败: 期望 {0:02X}, 实际 {1:02X}")]
+    Lrc(u8, u8),
+
+    #[error("CRC 校验失败: 期望 {0:04X}, 实际 {1:04X}")]
+    Crc(u16, u16),
+
+    #[error("从站地址不匹配: 期望 {expected}, 实际 {actual}")]
+    SlaveMismatch { expected: u8, actual: u8 },
+
+    #[error("功能码不匹配: 期望 {expected:#04X}, 实际 {actual:#04X}")]
+    FunctionMismatch {
+        expected: FunctionCode,
+        actual: FunctionCode,
+    },
+
+    #[error("从站 {slave} 未确认写入命令 {function:#04X}")]
+    WriteUnconfirmed { slave: u8, function: FunctionCode },
+
+    #[error("从站 {slave} 拒绝命令 {function:#04X}: {code}")]
+    Exception {
+        slave: u8,
+        function: FunctionCode,
+        code: ExceptionCode,
+    },
+
+    #[error("寄存器映射解析失败: {0}")]
+    Map(#[from] serde_json::Error),
+
+    #[error("寄存器映射中没有 {0}")]
+    PointNotFound(String),
+
+    #[error("寄存器点 {0} 不支持该读写操作")]
+    PointAccess(String),
+
+    #[error("寄存器点 {point} 的值 {value} 超出范围")]
+    OutOfRange { point: String, value: f64 },
+
+    #[error("电压模块布局无效: 起始寄存器 {register_start}, 通道数 {channels}")]
+    InvalidLayout {
+        register_start: u16,
+        channels: usize,
+    },
+
+    #[error("请求未完成: {0}")]
+    Aborted(String),
+
+    #[error("报文记录第 {line} 行解析失败: {source}")]
+    Capture {
+        line: usize,
+        source: serde_json::Error,
+    },
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Io(_) | Error::Serial(_) | Error::Aborted(_) => ErrorKind::Transport,
+            Error::Timeout => ErrorKind::Timeout,
+            Error::Lrc(..) | Error::Crc(..) => ErrorKind::Crc,
+            Error::Exception { .. } => ErrorKind::Exception,
+            Error::DataNull | Error::InvalidValue { .. } => ErrorKind::Decode,
+            Error::MbParseFail
+            | Error::DataShort(_)
+            | Error::DataLenError
+            | Error::ProtocolId(_)
+            | Error::AsciiFormat
+            | Error::SlaveMismatch { .. }
+            | Error::FunctionMismatch { .. }
+            | Error::WriteUnconfirmed { .. } => ErrorKind::Framing,
+            Error::Map(_)
+            | Error::PointNotFound(_)
+            | Error::PointAccess(_)
+            | Error::OutOfRange { .. }
+            | Error::InvalidLayout { .. }
+            | Error::Capture { .. } => ErrorKind::Invalid,
+        }
+    }
+
+    /// 设备没有响应，可以稍后重试
+    pub fn is_timeout(&self) -> bool {
+        self.kind() == ErrorKind::Timeout
+    }
+
+    /// 端口无法打开或已断开，需要重新连接
+    pub fn is_port_gone(&self) -> bool {
+        self.kind() == ErrorKind::Transport
+    }
+}
+
+/// 读取超时统一为 [`Error::Timeout`]
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Error::Timeout,
+            _ => Error::Io(e),
+        }
+    }
 }
 
 impl<T> From<Error> for crate::Result<T> {
     fn from(val: Error) -> Self {
-        Err(Box::new(val))
+        Err(val)
     }
 }
@@ -1,12 +1,25 @@
 // #![allow(dead_code)]
 // #![allow(unused_variables)]
 
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+pub type Result<T> = std::result::Result<T, error::Error>;
 
+#[cfg(feature = "tokio")]
+pub mod aio;
+pub mod capture;
+pub mod codec;
+pub mod coil;
+pub mod connection;
+pub mod device;
+pub mod diagnostics;
 pub mod error;
+pub mod policy;
 pub mod power;
 pub mod protocol;
+pub mod register_map;
 pub mod relay;
+pub mod scheduler;
 pub mod temperature;
+pub mod transport;
+pub mod units;
 pub mod utils;
 pub mod voltage;
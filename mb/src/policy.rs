@@ -0,0 +1,140 @@
+//! 请求策略
+//!
+//! 每个设备可以单独设定超时、重试次数、退避时间和请求间隔，由 [`Builder`](crate::protocol::Builder) 执行。
+//! 写入超时后从站可能已经执行，默认不重发。
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, ErrorKind},
+    protocol::{ExceptionCode, FunRequest},
+};
+
+/// 请求策略，时间单位为毫秒
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// 读取超时
+    pub timeout: u64,
+    /// 失败后的重试次数
+    pub retries: u8,
+    /// 重试退避，第 n 次重试前等待 `backoff * 2^(n-1)`
+    pub backoff: u64,
+    /// 同一端口两次请求之间的最小间隔
+    pub delay: u64,
+    /// 写入超时后是否重发，从站可能已经执行了写入
+    pub retry_writes: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            timeout: 300,
+            retries: 2,
+            backoff: 50,
+            delay: 0,
+            retry_writes: false,
+        }
+    }
+}
+
+impl Policy {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout.max(1))
+    }
+
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay)
+    }
+
+    /// 一次请求包括全部重试的最长耗时
+    pub fn budget(&self) -> Duration {
+        let attempts = self.retries as u32 + 1;
+        let backoff: Duration = (1..=self.retries).map(|n| self.backoff(n)).sum();
+        (self.timeout() + self.delay()) * attempts + backoff
+    }
+
+    /// 第 `attempt` 次重试前的等待时间，从 1 开始
+    pub fn backoff(&self, attempt: u8) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(self.backoff.saturating_mul(factor))
+    }
+
+    /// 错误是否值得重试
+    ///
+    /// 只重试超时、校验和帧格式错误，以及从站忙的异常；
+    /// 写入超时时从站可能已经执行，除非开启 `retry_writes` 否则不重发
+    pub fn should_retry(&self, request: &FunRequest, e: &Error) -> bool {
+        match e.kind() {
+            ErrorKind::Timeout => self.retry_writes || !request.code().is_write(),
+            ErrorKind::Crc | ErrorKind::Framing => true,
+            ErrorKind::Exception => matches!(
+                e,
+                Error::Exception {
+                    code: ExceptionCode::ServerDeviceBusy | ExceptionCode::Acknowledge,
+                    ..
+                }
+            ),
+            ErrorKind::Transport | ErrorKind::Decode | ErrorKind::Invalid => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::{Function, FunctionCode};
+
+    fn exception(code: ExceptionCode) -> Error {
+        Error::Exception {
+            slave: 0x01,
+            function: FunctionCode::ReadHoldingRegisters,
+            code,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        let policy = Policy {
+            backoff: 50,
+            ..Default::default()
+        };
+        let backoff: Vec<u64> = (1..=4)
+            .map(|n| policy.backoff(n).as_millis() as u64)
+            .collect();
+        assert_eq!(vec![50, 100, 200, 400], backoff);
+    }
+
+    #[test]
+    fn retry_busy_only() {
+        let policy = Policy::default();
+        let read = Function::new(0x01, FunctionCode::ReadHoldingRegisters, vec![0, 1]);
+        assert!(policy.should_retry(&read, &Error::Timeout));
+        assert!(policy.should_retry(&read, &Error::Crc(0, 1)));
+        assert!(policy.should_retry(&read, &Error::DataShort(2)));
+        assert!(!policy.should_retry(&read, &Error::DataNull));
+        assert!(!policy.should_retry(&read, &Error::Aborted(String::new())));
+
+        let retry = |code| policy.should_retry(&read, &exception(code));
+        assert!(retry(ExceptionCode::ServerDeviceBusy));
+        assert!(retry(ExceptionCode::Acknowledge));
+        assert!(!retry(ExceptionCode::IllegalFunction));
+        assert!(!retry(ExceptionCode::IllegalDataAddress));
+    }
+
+    #[test]
+    fn retry_write_timeout() {
+        let write = Function::new(0x01, FunctionCode::WriteSingleRegister, vec![0, 1]);
+        let policy = Policy::default();
+        assert!(!policy.should_retry(&write, &Error::Timeout));
+        assert!(policy.should_retry(&write, &Error::Crc(0, 1)));
+
+        let policy = Policy {
+            retry_writes: true,
+            ..Default::default()
+        };
+        assert!(policy.should_retry(&write, &Error::Timeout));
+    }
+}
@@ -1,23 +1,252 @@
 //!  电源
+//!
+//! 保持寄存器：
+//!
+//! | 地址 | 内容 |
+//! | --- | --- |
+//! | 2 | 温度 f32 |
+//! | 4 | 实际电压 f32 |
+//! | 6 | 实际电流 f32 |
+//! | 8 | 保护标志，见 [`Protection`]，可选 |
+//! | 9 | 输出，bit0 输出开启，bit1 远程控制 |
+//! | 10 | 设定电压 f32 |
+//! | 12 | 设定电流 f32 |
+//! | 14 | 输出模式，见 [`OutputMode`]，可选 |
+//!
+//! 没有厂家手册可以核对，以下内容均取自原有驱动或为推测：
+//!
+//! - 地址 2、4、6、9、10、12 取自原有驱动
+//! - 原有驱动向地址 9 写入 3 开启输出，bit0 输出开启、bit1 远程控制以及关闭时写入 2 为推测
+//! - 地址 8 和 14 为推测，并非所有电源都有，核对电源手册后在 [`PowerFeatures`] 中开启
+//!
+//! [`PowerMode::Status`] 只读取开启的寄存器，不读取地址 8 时分为 2 ~ 7 和 9 ~ 13 两次读取。
 
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
+    device::Device,
     error::Error,
     protocol::{FunRequest, FunResponse, Function, FunctionCode},
+    register_map::WordOrder,
+    units::{Amps, Celsius, Volts, Watts},
     utils::current_timestamp,
 };
 
 pub struct Power;
 
-impl Power {
-    pub fn request(slave: u8, mode: &PowerMode) -> FunRequest {
-        let mode = mode.params();
-        Function::new(slave, mode.0, mode.1)
+impl Device for Power {
+    type Command = PowerCommand;
+    type Output = PowerReply;
+
+    /// 分块读取的状态只有第一个请求，完整请求见 [`Device::requests`]
+    fn request(slave: u8, command: &PowerCommand) -> FunRequest {
+        let (code, data) = command.params().swap_remove(0);
+        Function::new(slave, code, data)
+    }
+
+    fn requests(slave: u8, command: &PowerCommand) -> Vec<FunRequest> {
+        command
+            .params()
+            .into_iter()
+            .map(|(code, data)| Function::new(slave, code, data))
+            .collect()
+    }
+
+    fn decode(command: &PowerCommand, response: FunResponse) -> crate::Result<PowerReply> {
+        Ok(match command.mode {
+            PowerMode::Status => return Self::decode_all(command, vec![response]),
+            PowerMode::SetOutput(_)
+            | PowerMode::SetOutputMode(_)
+            | PowerMode::SetVoltage(_)
+            | PowerMode::SetCurrent(_) => PowerReply::Written,
+            mode => PowerReply::Value(PowerData::decode(&response, &mode, command.word_order)?),
+        })
+    }
+
+    fn decode_all(
+        command: &PowerCommand,
+        responses: Vec<FunResponse>,
+    ) -> crate::Result<PowerReply> {
+        if command.mode != PowerMode::Status {
+            let response = responses.into_iter().next().ok_or(Error::DataNull)?;
+            return Self::decode(command, response);
+        }
+
+        // 各块按照地址放回，未读取的寄存器为 0
+        let blocks = command.features.status_blocks();
+        if responses.len() < blocks.len() {
+            return Err(Error::DataNull);
+        }
+        let mut data = vec![0; (MODE_ADDRESS - STATUS_ADDRESS + 1) as usize];
+        for ((address, words), response) in blocks.into_iter().zip(&responses) {
+            let (offset, words) = ((address - STATUS_ADDRESS) as usize, words as usize);
+            let block = response.data();
+            if block.len() < words {
+                return Err(Error::DataShort(block.len()));
+            }
+            data[offset..offset + words].copy_from_slice(&block[..words]);
+        }
+
+        let status = PowerStatus::decode(&data, command.word_order, command.features)?;
+        Ok(PowerReply::Status(status))
+    }
+}
+
+/// 按照命令区分的响应
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerReply {
+    /// 单个读数
+    Value(PowerData),
+    /// 全部状态
+    Status(PowerStatus),
+    /// 写入已确认
+    Written,
+}
+
+/// 状态起始地址
+const STATUS_ADDRESS: u16 = 2;
+/// 状态必读寄存器的最后一个地址，设定电流的低位
+const STATUS_END: u16 = 13;
+/// 保护标志地址
+const PROTECTION_ADDRESS: u16 = 8;
+/// 输出地址
+const OUTPUT_ADDRESS: u16 = 9;
+/// 输出模式地址
+const MODE_ADDRESS: u16 = 0x000E;
+
+/// 一个 f32 占用的寄存器数量
+const F32_WORDS: u16 = 2;
+
+/// 输出开启
+const OUTPUT_ON: u16 = 0x0001;
+/// 远程控制，写入输出时始终保持
+const OUTPUT_REMOTE: u16 = 0x0002;
+
+/// 电源的可选寄存器，默认都不读取
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerFeatures {
+    /// 地址 8 保护标志
+    #[serde(default)]
+    pub protection: bool,
+    /// 地址 14 输出模式
+    #[serde(default)]
+    pub output_mode: bool,
+}
+
+impl PowerFeatures {
+    /// 状态读取的最后一个地址
+    fn status_end(&self) -> u16 {
+        if self.output_mode {
+            MODE_ADDRESS
+        } else {
+            STATUS_END
+        }
+    }
+
+    /// [`PowerMode::Status`] 读取的寄存器块 (起始地址, 数量)，跳过未开启的可选寄存器
+    pub fn status_blocks(&self) -> Vec<(u16, u16)> {
+        let end = self.status_end();
+        if self.protection {
+            vec![(STATUS_ADDRESS, end - STATUS_ADDRESS + 1)]
+        } else {
+            vec![
+                (STATUS_ADDRESS, PROTECTION_ADDRESS - STATUS_ADDRESS),
+                (OUTPUT_ADDRESS, end - OUTPUT_ADDRESS + 1),
+            ]
+        }
+    }
+}
+
+/// 命令与浮点数的字序，不同厂家的电源字序不同
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerCommand {
+    pub mode: PowerMode,
+    pub word_order: WordOrder,
+    /// 状态中读取的可选寄存器
+    pub features: PowerFeatures,
+}
+
+impl PowerCommand {
+    pub fn new(mode: PowerMode, word_order: WordOrder) -> Self {
+        Self {
+            mode,
+            word_order,
+            features: PowerFeatures::default(),
+        }
+    }
+
+    pub fn with_features(mut self, features: PowerFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// 命令的全部请求，只有 [`PowerMode::Status`] 可能有多个
+    pub fn params(&self) -> Vec<(FunctionCode, Vec<u16>)> {
+        let read = |address: u16, words: u16| {
+            (
+                FunctionCode::ReadHoldingRegisters,
+                [address, words].to_vec(),
+            )
+        };
+        let params = match self.mode {
+            PowerMode::Temp => read(2, F32_WORDS),
+            PowerMode::Voltage => read(4, F32_WORDS),
+            PowerMode::Current => read(6, F32_WORDS),
+
+            PowerMode::GetOnOff => read(OUTPUT_ADDRESS, 0x0003),
+            PowerMode::GetVoltage => read(0x000A, F32_WORDS),
+            PowerMode::GetCurrent => read(0x000C, F32_WORDS),
+
+            PowerMode::Status => {
+                return self
+                    .features
+                    .status_blocks()
+                    .into_iter()
+                    .map(|(address, words)| read(address, words))
+                    .collect();
+            }
+
+            PowerMode::SetOutput(on) => {
+                let value = if on {
+                    OUTPUT_REMOTE | OUTPUT_ON
+                } else {
+                    OUTPUT_REMOTE
+                };
+                (
+                    FunctionCode::WriteMultipleRegisters,
+                    [OUTPUT_ADDRESS, value].to_vec(),
+                )
+            }
+            PowerMode::SetOutputMode(mode) => (
+                FunctionCode::WriteMultipleRegisters,
+                [MODE_ADDRESS, mode as u16].to_vec(),
+            ),
+            PowerMode::SetVoltage(n) => {
+                let f = f32_u16(n.value(), self.word_order);
+                let data = vec![0x000A, f[0], f[1]];
+                (FunctionCode::WriteMultipleRegisters, data)
+            }
+            PowerMode::SetCurrent(n) => {
+                let f = f32_u16(n.value(), self.word_order);
+                let data = vec![0x000C, f[0], f[1]];
+                (FunctionCode::WriteMultipleRegisters, data)
+            }
+        };
+        vec![params]
+    }
+}
+
+/// 大端字序 ABCD
+impl From<PowerMode> for PowerCommand {
+    fn from(mode: PowerMode) -> Self {
+        Self::new(mode, WordOrder::default())
     }
 }
 
 /// 命令请求类型
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PowerMode {
     ///实际温度
     Temp,
@@ -33,84 +262,222 @@ pub enum PowerMode {
     /// 获取设置的电流
     GetCurrent,
 
-    /// 远程启动
-    SetOnOff,
+    /// 一次读取实际值、设定值、输出和保护状态
+    Status,
+
+    /// 开启或关闭输出
+    SetOutput(bool),
+    /// 设定交流或直流输出
+    SetOutputMode(OutputMode),
     /// 设定电压
-    SetVoltage(f32),
+    SetVoltage(Volts),
     /// 设定电流
-    SetCurrent(f32),
+    SetCurrent(Amps),
 }
 
-impl PowerMode {
-    pub fn params(&self) -> (FunctionCode, Vec<u16>) {
-        match self {
-            PowerMode::Temp => (FunctionCode::ReadHoldingRegisters, [2, 0].to_vec()),
-            PowerMode::Voltage => (FunctionCode::ReadHoldingRegisters, [4, 0].to_vec()),
-            PowerMode::Current => (FunctionCode::ReadHoldingRegisters, [6, 0xC000].to_vec()),
-
-            PowerMode::GetOnOff => (FunctionCode::ReadHoldingRegisters, [9, 0x0003].to_vec()),
-            PowerMode::GetVoltage => (FunctionCode::ReadHoldingRegisters, [0x000A, 0].to_vec()),
-            PowerMode::GetCurrent => (FunctionCode::ReadHoldingRegisters, [0x000C, 0].to_vec()),
+/// 浮点数转换为两个寄存器
+pub fn f32_u16(v: f32, order: WordOrder) -> [u16; 2] {
+    order.to_words(v.to_bits())
+}
 
-            PowerMode::SetOnOff => (FunctionCode::WriteMultipleRegisters, [9, 0x0003].to_vec()),
-            PowerMode::SetVoltage(n) => {
-                let f = f32_u16(*n);
-                let data = vec![0x000A, f[0], f[1]];
-                (FunctionCode::WriteMultipleRegisters, data)
-            }
-            PowerMode::SetCurrent(n) => {
-                let f = f32_u16(*n);
-                let data = vec![0x000C, f[0], f[1]];
-                (FunctionCode::WriteMultipleRegisters, data)
-            }
-        }
-    }
+/// 两个寄存器转换为浮点数
+pub fn u16_f32(words: [u16; 2], order: WordOrder) -> f32 {
+    f32::from_bits(order.from_words(words))
 }
 
-// 32 转 16
-pub fn f32_u16(v: f32) -> [u16; 2] {
-    let b = v.to_bits();
-    let bytes = b.to_be_bytes();
-    let u16_array: [u16; 2] = [
-        u16::from_be_bytes(bytes[0..2].try_into().unwrap()),
-        u16::from_be_bytes(bytes[2..4].try_into().unwrap()),
-    ];
-    u16_array
+/// 电源读数，类型由读取命令决定
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub enum PowerValue {
+    Temp(Celsius),
+    Voltage(Volts),
+    Current(Amps),
+    /// 输出是否开启
+    Output(bool),
 }
 
 /// 电源
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub struct PowerData {
     pub time: Duration,
-    pub value: f32,
+    pub value: PowerValue,
 }
 
-impl TryFrom<FunResponse> for PowerData {
-    type Error = Box<dyn std::error::Error + Send + Sync>;
+impl PowerData {
+    /// 按照读取命令和字序解析，写入命令和 [`PowerMode::Status`] 没有单个读数
+    pub fn decode(
+        response: &FunResponse,
+        mode: &PowerMode,
+        order: WordOrder,
+    ) -> crate::Result<Self> {
+        let data = response.data();
+        let first = data.first().copied().ok_or(Error::DataNull)?;
+        let float = || match data.get(..2) {
+            Some(words) => Ok(u16_f32([words[0], words[1]], order)),
+            None => Err(Error::DataShort(data.len())),
+        };
 
-    fn try_from(value: FunResponse) -> std::result::Result<Self, Self::Error> {
-        let bytes = value.data_u8();
+        let value = match mode {
+            PowerMode::Temp => PowerValue::Temp(Celsius::new(float()?)),
+            PowerMode::Voltage | PowerMode::GetVoltage => PowerValue::Voltage(Volts::new(float()?)),
+            PowerMode::Current | PowerMode::GetCurrent => PowerValue::Current(Amps::new(float()?)),
+            PowerMode::GetOnOff => PowerValue::Output(first & OUTPUT_ON != 0),
+            _ => return Err(Error::MbParseFail),
+        };
 
-        let data: Vec<f32> = bytes
-            .as_slice()
-            .chunks(4)
-            .filter_map(|bs| {
-                if bs.len() == 4 {
-                    let b: [u8; 4] = bs.try_into().unwrap();
-                    Some(f32::from_be_bytes(b))
-                } else {
-                    None
-                }
-            })
-            .collect();
+        Ok(PowerData {
+            time: current_timestamp(),
+            value,
+        })
+    }
+}
+
+/// 输出模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display)]
+pub enum OutputMode {
+    /// 交流
+    #[default]
+    Ac = 0,
+    /// 直流
+    Dc = 1,
+}
+
+impl TryFrom<u16> for OutputMode {
+    type Error = crate::error::Error;
+
+    fn try_from(value: u16) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(OutputMode::Ac),
+            1 => Ok(OutputMode::Dc),
+            _ => Err(Error::InvalidValue {
+                address: MODE_ADDRESS,
+                value,
+            }),
+        }
+    }
+}
+
+/// 保护标志，bit0 过压，bit1 过流，bit2 过温
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Protection {
+    /// 过压保护
+    pub ovp: bool,
+    /// 过流保护
+    pub ocp: bool,
+    /// 过温保护
+    pub otp: bool,
+}
+
+impl Protection {
+    pub fn from_bits(bits: u16) -> Self {
+        Protection {
+            ovp: bits & 0b001 != 0,
+            ocp: bits & 0b010 != 0,
+            otp: bits & 0b100 != 0,
+        }
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.ovp as u16 | (self.ocp as u16) << 1 | (self.otp as u16) << 2
+    }
+
+    /// 是否有保护动作
+    pub fn any(&self) -> bool {
+        self.ovp || self.ocp || self.otp
+    }
+}
+
+/// 电源状态
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PowerStatus {
+    pub time: Duration,
+    /// 温度
+    pub temp: Celsius,
+    /// 实际电压
+    pub voltage: Volts,
+    /// 实际电流
+    pub current: Amps,
+    /// 设定电压
+    pub set_voltage: Volts,
+    /// 设定电流
+    pub set_current: Amps,
+    /// 输出是否开启
+    pub output: bool,
+    /// 输出模式，未开启或读数无效时为空
+    pub mode: Option<OutputMode>,
+    /// 保护标志，未开启时为空
+    pub protection: Option<Protection>,
+}
 
-        let data = data.first().ok_or(Box::new(Error::DataNull))?;
+impl PowerStatus {
+    /// 实际输出功率
+    pub fn power(&self) -> Watts {
+        self.voltage * self.current
+    }
 
-        let dur = current_timestamp();
-        let temp = PowerData {
-            time: dur,
-            value: *data,
+    /// 解析地址 2 起的状态寄存器，可选寄存器不影响其余读数
+    pub fn decode(data: &[u16], order: WordOrder, features: PowerFeatures) -> crate::Result<Self> {
+        if data.len() < (features.status_end() - STATUS_ADDRESS + 1) as usize {
+            return Err(Error::DataShort(data.len()));
+        }
+
+        // 相对起始地址 2 的偏移
+        let f32_at = |offset: usize| u16_f32([data[offset], data[offset + 1]], order);
+
+        let mode = if features.output_mode {
+            OutputMode::try_from(data[12])
+                .inspect_err(|e| log::warn!("电源输出模式无效: {e}"))
+                .ok()
+        } else {
+            None
         };
-        Ok(temp)
+
+        Ok(PowerStatus {
+            time: current_timestamp(),
+            temp: Celsius::new(f32_at(0)),
+            voltage: Volts::new(f32_at(2)),
+            current: Amps::new(f32_at(4)),
+            protection: features.protection.then(|| Protection::from_bits(data[6])),
+            output: data[7] & OUTPUT_ON != 0,
+            set_voltage: Volts::new(f32_at(8)),
+            set_current: Amps::new(f32_at(10)),
+            mode,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn word_order() {
+        // 12.5 = 0x4148_0000
+        let command = PowerCommand::new(PowerMode::SetVoltage(Volts::new(12.5)), WordOrder::CDAB);
+        let request = Power::request(0x03, &command);
+        assert_eq!(vec![0x000A, 0x0000, 0x4148], request.data());
+
+        let response = Function::new(
+            0x03,
+            FunctionCode::ReadHoldingRegisters,
+            vec![0x0000, 0x4148],
+        );
+        let data =
+            PowerData::decode(&response, &PowerMode::GetVoltage, command.word_order).unwrap();
+        assert_eq!(PowerValue::Voltage(Volts::new(12.5)), data.value);
+        let data = PowerData::decode(&response, &PowerMode::GetVoltage, WordOrder::ABCD).unwrap();
+        assert_ne!(PowerValue::Voltage(Volts::new(12.5)), data.value);
+        assert!(PowerData::decode(&response, &command.mode, command.word_order).is_err());
+
+        // 浮点数读取两个寄存器
+        for mode in [
+            PowerMode::Temp,
+            PowerMode::Voltage,
+            PowerMode::Current,
+            PowerMode::GetVoltage,
+            PowerMode::GetCurrent,
+        ] {
+            let request = Power::request(0x03, &mode.into());
+            assert_eq!(Some(&F32_WORDS), request.data().get(1), "{mode:?}");
+        }
     }
 }
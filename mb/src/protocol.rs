@@ -1,92 +1,290 @@
 //! modbus 协议相关实现
 
 use core::fmt;
-use serialport::{SerialPort, SerialPortType};
+use serialport::SerialPortType;
+
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::error::Error;
 use crate::Result;
+use crate::capture::{Capture, Recorder};
+use crate::codec::decode_frame;
+pub use crate::codec::{
+    Framing, MBAP_LEN, Mbap, calculate_crc, calculate_lrc, encode_frame, rtu_response_len,
+    split_frame,
+};
+use crate::connection::{Connection, SharedConnection, get_connection, lock};
+use crate::device::Device;
+use crate::error::Error;
+use crate::policy::Policy;
+use crate::transport::{Endpoint, Transport};
 
 #[derive(Debug, Clone)]
 pub struct Builder {
-    pub port_name: String,
-    pub baudrate: u32,
+    pub endpoint: Endpoint,
+    pub framing: Framing,
+    /// 超时、重试与请求间隔
+    pub policy: Policy,
+    /// 自定义传输的连接，不使用连接池
+    conn: Option<SharedConnection>,
+    /// 收发记录
+    capture: Option<Capture>,
 }
 
 impl Builder {
-    pub fn new<T: Into<String>>(port_name: T, baudrate: u32) -> Self {
+    /// 串口，`tcp://host:port` 形式的名称使用 Modbus TCP
+    pub fn new<T: AsRef<str>>(port_name: T, baudrate: u32) -> Self {
+        let endpoint = Endpoint::parse(port_name, baudrate);
+        let framing = match endpoint {
+            Endpoint::Tcp { .. } => Framing::Tcp,
+            _ => Framing::Rtu,
+        };
+
+        Self {
+            endpoint,
+            framing,
+            policy: Policy::default(),
+            conn: None,
+            capture: None,
+        }
+    }
+
+    /// Modbus TCP `host:port`
+    pub fn tcp<T: Into<String>>(addr: T) -> Self {
+        Self {
+            endpoint: Endpoint::tcp(addr),
+            framing: Framing::Tcp,
+            policy: Policy::default(),
+            conn: None,
+            capture: None,
+        }
+    }
+
+    /// 使用自定义传输，例如 [`Loopback`](crate::transport::Loopback)
+    pub fn with_transport<N, T>(name: N, transport: T) -> Self
+    where
+        N: Into<String>,
+        T: Transport + 'static,
+    {
+        let conn = Connection::with_transport(name, Box::new(transport));
         Self {
-            port_name: port_name.into(),
-            baudrate,
+            endpoint: conn.endpoint().clone(),
+            framing: Framing::Rtu,
+            policy: Policy::default(),
+            conn: Some(Arc::new(Mutex::new(conn))),
+            capture: None,
         }
     }
 
+    /// 设定帧格式
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// 设定请求策略
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// 记录每次收发的原始数据，见 [`capture`](crate::capture)
+    pub fn capture(mut self, capture: Capture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// 发送设备命令并解析响应
+    pub fn execute<D: Device>(&self, slave: u8, command: &D::Command) -> Result<D::Output> {
+        let responses = D::requests(slave, command)
+            .iter()
+            .map(|request| self.call(request))
+            .collect::<Result<Vec<_>>>()?;
+        D::decode_all(command, responses)
+    }
+
     /// 发送请求，读取数据后，将数据转化
+    ///
+    /// 端口由 [`connection`](crate::connection) 统一管理，同一 (端口, 波特率) 复用一个句柄。
+    /// 失败时按照 [`Policy`] 退避后重试。
     pub fn call(&self, request: &FunRequest) -> Result<FunResponse> {
-        let mut port = serialport::new(self.port_name.clone(), self.baudrate)
-            .timeout(Duration::from_millis(300))
-            .open()?;
+        let conn = match &self.conn {
+            Some(conn) => conn.clone(),
+            None => get_connection(&self.endpoint),
+        };
 
-        let _i = port.write(&request.request_data())?;
+        let mut attempt = 0;
+        loop {
+            match self.call_once(&conn, request) {
+                Err(e)
+                    if attempt < self.policy.retries && self.policy.should_retry(request, &e) =>
+                {
+                    attempt += 1;
+                    log::warn!(
+                        "{} 从站 {} 请求失败，第 {attempt} 次重试: {e}",
+                        self.endpoint,
+                        request.slave
+                    );
+                    std::thread::sleep(self.policy.backoff(attempt));
+                }
+                result => return result,
+            }
+        }
+    }
 
-        port.flush().unwrap();
-        // thread::sleep(Duration::from_millis(100));
+    fn call_once(&self, conn: &SharedConnection, request: &FunRequest) -> Result<FunResponse> {
+        let framing = self.framing;
+        let timeout = self.policy.timeout();
+        let gap = endpoint_gap(&self.endpoint);
+        let transaction = next_transaction();
+        let frame = request.request_frame(framing, transaction);
 
-        let mut response = vec![0u8; 1024];
-        let n = read_full_response(&mut port, &mut response)?;
-        port.flush().unwrap();
+        let response = {
+            let mut conn = lock(conn);
+            conn.wait(self.policy.delay());
+            conn.exchange(|port| {
+                let exchange = |port: &mut dyn Transport| {
+                    port.set_timeout(timeout)?;
 
-        // 只保留实际读取到的字节
-        response.truncate(n);
+                    // 丢弃上次残留的数据
+                    port.clear_input()?;
 
-        // 如果是命令则？
-        // print_hex("re res:", &response.to_vec());
-        let response = match request.code {
-            FunctionCode::ReadCoils
-            | FunctionCode::ReadDiscreteInputs
-            | FunctionCode::ReadHoldingRegisters
-            | FunctionCode::ReadInputRegisters => Function::parse_response(&response)?,
-            FunctionCode::WriteSingleCoil
-            | FunctionCode::ReadWriteMultipleRegisters
-            | FunctionCode::WriteSingleRegister
-            | FunctionCode::WriteMultipleCoils
-            | FunctionCode::WriteMultipleRegisters
-            | FunctionCode::MaskWriteRegister
-            | FunctionCode::Custom(_) => request.clone(),
+                    port.write_all(&frame)?;
+                    port.flush()?;
+
+                    match framing {
+                        Framing::Rtu | Framing::RtuOverTcp => read_rtu_frame(port, timeout, gap),
+                        Framing::Ascii => read_ascii_frame(port),
+                        Framing::Tcp => read_mbap_frame(port, transaction),
+                    }
+                };
+
+                match &self.capture {
+                    Some(capture) => {
+                        let port_name = self.endpoint.to_string();
+                        let mut recorder = Recorder::new(port, capture, port_name, framing);
+                        let result = exchange(&mut recorder);
+                        recorder.finish();
+                        result
+                    }
+                    None => exchange(port),
+                }
+            })?
         };
 
-        // let response = Function::parse_response(&response)?;
+        request.decode_response(framing, &response)
+    }
+}
 
-        Ok(response)
+/// 生成 Modbus TCP 事务号
+pub(crate) fn next_transaction() -> u16 {
+    static TRANSACTION: AtomicU16 = AtomicU16::new(0);
+    TRANSACTION.fetch_add(1, Ordering::Relaxed).wrapping_add(1)
+}
+
+/// 非串口传输（RTU over TCP 等）的帧间静默时间
+const SOCKET_FRAME_GAP: Duration = Duration::from_millis(20);
+
+/// RTU 帧间静默时间: 3.5 个字符（每字符 11 位），波特率高于 19200 时固定为 1.75ms
+pub fn frame_gap(baudrate: u32) -> Duration {
+    if baudrate > 19200 {
+        Duration::from_micros(1750)
+    } else {
+        Duration::from_micros(38_500_000 / baudrate.max(1) as u64)
     }
 }
 
-// ch340 32位字节缓存读取
-fn read_full_response(port: &mut Box<dyn SerialPort>, buffer: &mut [u8]) -> Result<usize> {
-    let mut total_read = 0;
-    let mut read_buffer = [0u8; 32];
+/// 按照连接目标选择帧间静默时间
+pub(crate) fn endpoint_gap(endpoint: &Endpoint) -> Duration {
+    match endpoint {
+        Endpoint::Serial { baudrate, .. } => frame_gap(*baudrate),
+        _ => SOCKET_FRAME_GAP,
+    }
+}
 
-    loop {
-        match port.read(&mut read_buffer) {
-            Ok(n) => {
-                if n == 0 {
-                    break;
-                }
-                // log::debug!("--- {n} {:?}", read_buffer);
-                buffer[total_read..total_read + n].copy_from_slice(&read_buffer[..n]);
-                total_read += n;
-                if n < read_buffer.len() {
-                    break; // 读取完成
+/// 读取一帧 RTU 响应
+///
+/// 已知功能码时按照长度读取，否则在超过帧间静默时间 `gap` 没有数据时结束。
+/// 长度之后多余的数据会被丢弃。
+fn read_rtu_frame(port: &mut dyn Transport, timeout: Duration, gap: Duration) -> Result<Vec<u8>> {
+    let mut frame = Vec::with_capacity(256);
+    let mut buffer = [0u8; 256];
+
+    let result = loop {
+        let len = rtu_response_len(&frame);
+        if let Some(len) = len
+            && frame.len() >= len
+        {
+            frame.truncate(len);
+            break Ok(());
+        }
+
+        // 长度未知时使用帧间静默判断帧结束
+        port.set_timeout(if len.is_none() { gap } else { timeout })?;
+
+        match port.read(&mut buffer) {
+            Ok(0) => break Ok(()),
+            Ok(n) => frame.extend_from_slice(&buffer[..n]),
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                if frame.is_empty() {
+                    break Err(Error::Timeout);
                 }
+                // 帧不完整时交给 CRC 校验报告
+                break Ok(());
             }
+            Err(e) => break Err(e.into()),
+        }
+    };
+
+    port.set_timeout(timeout)?;
+    result?;
+
+    Ok(frame)
+}
+
+/// 读取 ASCII 帧，直到 LF
+fn read_ascii_frame(port: &mut dyn Transport) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let mut read_buffer = [0u8; 64];
+
+    while !buffer.contains(&b'\n') {
+        match port.read(&mut read_buffer) {
+            Ok(0) => break,
+            Ok(n) => buffer.extend_from_slice(&read_buffer[..n]),
             Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                break; // 读取超时，退出循环
+                if buffer.is_empty() {
+                    return Err(Error::Timeout);
+                }
+                break;
             }
-            Err(e) => return Err(Box::new(e)),
+            Err(e) => return Err(e.into()),
         }
     }
 
-    Ok(total_read)
+    Ok(buffer)
+}
+
+/// 按照 MBAP 头中的长度读取一帧，丢弃事务号不匹配的旧响应
+fn read_mbap_frame(port: &mut dyn Transport, transaction: u16) -> Result<Vec<u8>> {
+    loop {
+        let mut frame = vec![0u8; MBAP_LEN];
+        port.read_exact(&mut frame)?;
+
+        let header = Mbap::parse(&frame)?;
+        let mut pdu = vec![0u8; header.pdu_len()];
+        port.read_exact(&mut pdu)?;
+        frame.extend_from_slice(&pdu);
+
+        if header.transaction == transaction {
+            return Ok(frame);
+        }
+
+        log::warn!(
+            "丢弃事务号不匹配的响应: 期望 {transaction}, 实际 {}",
+            header.transaction
+        );
+    }
 }
 
 /// Modbus Function
@@ -111,6 +309,47 @@ impl Function {
         }
     }
 
+    /// 掩码写入寄存器 `0x16`，从站原子地修改寄存器的部分位
+    ///
+    /// 结果为 `(当前值 & and_mask) | (or_mask & !and_mask)`，参看 [`mask_value`]
+    pub fn mask_write(slave: u8, address: u16, and_mask: u16, or_mask: u16) -> Self {
+        Self::new(
+            slave,
+            FunctionCode::MaskWriteRegister,
+            vec![address, and_mask, or_mask],
+        )
+    }
+
+    /// 读/写多个寄存器 `0x17`，从站先写入再读取，响应为读取的寄存器
+    pub fn read_write_multiple(
+        slave: u8,
+        read_address: u16,
+        read_quantity: u16,
+        write_address: u16,
+        values: &[u16],
+    ) -> Self {
+        let header = [
+            read_address,
+            read_quantity,
+            write_address,
+            values.len() as u16,
+        ];
+        let mut data: Vec<u8> = header.iter().flat_map(|v| v.to_be_bytes()).collect();
+        data.push((values.len() * 2) as u8);
+        data.extend(values.iter().flat_map(|v| v.to_be_bytes()));
+        Self::with_bytes(slave, FunctionCode::ReadWriteMultipleRegisters, data)
+    }
+
+    /// 按字节构建，用于线圈等按位打包的数据
+    pub fn with_bytes(slave: u8, code: FunctionCode, data: Vec<u8>) -> Self {
+        Self {
+            slave,
+            code,
+            data_u16: u8_to_u16(&data),
+            data_u8: data,
+        }
+    }
+
     pub fn slave(&self) -> u8 {
         self.slave
     }
@@ -127,143 +366,331 @@ impl Function {
         self.data_u8.clone()
     }
 
+    /// 按位解包的数据，第一个字节的最低位在前
+    pub fn data_bits(&self) -> Vec<bool> {
+        unpack_bits(&self.data_u8, self.data_u8.len() * 8)
+    }
+
     // 解析Modbus响应数据，将其转换为 Function
     pub fn parse_response(response: &[u8]) -> Result<Self> {
-        let len = response.len();
-        if len < 5 {
-            return Err(Box::new(Error::DataShort(len))); // 响应数据太短
-        }
+        Self::parse_response_frame(Framing::Rtu, response)
+    }
+
+    /// 解析 Modbus TCP 响应，返回 (事务号, Function)
+    pub fn parse_response_tcp(response: &[u8]) -> Result<(u16, Self)> {
+        let frame = decode_frame(Framing::Tcp, response)?;
+        let fp = Self::parse_response_pdu(frame.slave, &frame.pdu)?;
+        Ok((frame.transaction, fp))
+    }
 
-        let byte_count = response[2] as usize;
-        if len < 3 + byte_count || byte_count % 2 != 0 {
-            return Err(Box::new(Error::DataLenError)); // 数据长度不匹配
+    /// 解析 Modbus ASCII 响应
+    pub fn parse_response_ascii(response: &[u8]) -> Result<Self> {
+        Self::parse_response_frame(Framing::Ascii, response)
+    }
+
+    /// 按照帧格式解析响应
+    pub fn parse_response_frame(framing: Framing, response: &[u8]) -> Result<Self> {
+        let frame = decode_frame(framing, response)?;
+        Self::parse_response_pdu(frame.slave, &frame.pdu)
+    }
+
+    /// 解析响应 PDU: 功能码 + 字节数 + 数据
+    fn parse_response_pdu(slave: u8, pdu: &[u8]) -> Result<Self> {
+        let len = pdu.len();
+        if len < 2 {
+            return Err(Error::DataShort(len)); // 响应数据太短
         }
 
-        let mut result = Vec::with_capacity(byte_count / 2);
-        for i in 0..(byte_count / 2) {
-            let high_byte = response[3 + 2 * i] as u16;
-            let low_byte = response[3 + 2 * i + 1] as u16;
-            result.push((high_byte << 8) | low_byte);
+        check_exception(slave, pdu)?;
+
+        let code = FunctionCode::new(pdu[0]);
+        let byte_count = pdu[1] as usize;
+        // 线圈和离散输入按位打包，字节数可以为奇数
+        if len < 2 + byte_count || (!code.is_bits() && !byte_count.is_multiple_of(2)) {
+            return Err(Error::DataLenError); // 数据长度不匹配
         }
 
+        let data_u8 = pdu[2..2 + byte_count].to_vec();
         let fp = Function {
-            slave: response[0],
-            code: FunctionCode::new(response[1]),
-            data_u16: result,
-            data_u8: response[3..3 + byte_count].to_vec(),
+            slave,
+            code,
+            data_u16: u8_to_u16(&data_u8),
+            data_u8,
         };
 
         Ok(fp)
     }
 
-    /// 将数据解析为请求 Function
-    pub fn parse_request(request: &[u8]) -> Result<Self> {
-        let len = request.len();
-        if len < 4 {
-            return Err(Box::new(Error::DataShort(len))); // 响应数据太短
+    /// 解析本请求的响应帧
+    ///
+    /// 校验 CRC/LRC，确认从站与功能码和请求一致，读取命令解析数据，写入命令检查确认
+    pub fn decode_response(&self, framing: Framing, response: &[u8]) -> Result<FunResponse> {
+        let (slave, pdu) = split_frame(framing, response)?;
+        if slave != self.slave {
+            return Err(Error::SlaveMismatch {
+                expected: self.slave,
+                actual: slave,
+            });
         }
+        check_exception(slave, &pdu)?;
+        if pdu[0] != self.code.value() {
+            return Err(Error::FunctionMismatch {
+                expected: self.code,
+                actual: FunctionCode::new(pdu[0]),
+            });
+        }
+
+        // print_hex("re res:", &response.to_vec());
+        let response = match self.code {
+            FunctionCode::ReadCoils
+            | FunctionCode::ReadDiscreteInputs
+            | FunctionCode::ReadHoldingRegisters
+            | FunctionCode::ReadInputRegisters
+            | FunctionCode::ReadWriteMultipleRegisters => {
+                Function::parse_response_pdu(slave, &pdu)?
+            }
+            FunctionCode::WriteSingleCoil
+            | FunctionCode::WriteSingleRegister
+            | FunctionCode::WriteMultipleCoils
+            | FunctionCode::WriteMultipleRegisters
+            | FunctionCode::MaskWriteRegister => Function::parse_write_ack(self, &pdu)?,
+            // 诊断和设备标识的响应格式各不相同，由各自的数据类型解析
+            FunctionCode::Diagnostics
+            | FunctionCode::GetCommEventCounter
+            | FunctionCode::ReadDeviceIdentification
+            | FunctionCode::Custom(_) => {
+                let data_u8 = pdu[1..].to_vec();
+                Function {
+                    slave,
+                    code: self.code,
+                    data_u16: u8_to_u16(&data_u8),
+                    data_u8,
+                }
+            }
+        };
 
-        // 掐头去尾
-        let byte_count = request.len() - 4_usize;
-        if byte_count < 2 || byte_count % 2 != 0 {
-            return Err(Box::new(Error::DataLenError)); // 数据长度不匹配
+        Ok(response)
+    }
+
+    /// 解析写入命令的确认响应 PDU，与请求不一致时视为设备未确认
+    ///
+    /// 单个写入和掩码写入为请求回显，多个写入为 地址 + 数量
+    pub fn parse_write_ack(request: &FunRequest, pdu: &[u8]) -> Result<Self> {
+        if pdu != request.write_ack_pdu().as_slice() {
+            return Err(Error::WriteUnconfirmed {
+                slave: request.slave,
+                function: request.code,
+            });
         }
-        // log::trace!("byte_count: {}, {}", byte_count, request.len());
-        let mut result = Vec::with_capacity(byte_count / 2);
-        for i in 0..(byte_count / 2) {
-            let high_byte = request[2 + 2 * i] as u16;
-            let low_byte = request[2 + 2 * i + 1] as u16;
-            result.push((high_byte << 8) | low_byte);
+
+        let data_u8 = pdu[1..].to_vec();
+        let fp = Function {
+            slave: request.slave,
+            code: request.code,
+            data_u16: u8_to_u16(&data_u8),
+            data_u8,
+        };
+
+        Ok(fp)
+    }
+
+    /// 解析 Modbus TCP 请求，返回 (事务号, Function)
+    pub fn parse_request_tcp(request: &[u8]) -> Result<(u16, Self)> {
+        let frame = decode_frame(Framing::Tcp, request)?;
+        let fp = Self::parse_request_pdu(frame.slave, &frame.pdu)?;
+        Ok((frame.transaction, fp))
+    }
+
+    /// 解析 Modbus ASCII 请求
+    pub fn parse_request_ascii(request: &[u8]) -> Result<Self> {
+        Self::parse_request_frame(Framing::Ascii, request)
+    }
+
+    /// 按照帧格式解析请求
+    pub fn parse_request_frame(framing: Framing, request: &[u8]) -> Result<Self> {
+        let frame = decode_frame(framing, request)?;
+        Self::parse_request_pdu(frame.slave, &frame.pdu)
+    }
+
+    /// 解析请求 PDU: 功能码 + 数据
+    fn parse_request_pdu(slave: u8, pdu: &[u8]) -> Result<Self> {
+        let Some((&code, data)) = pdu.split_first() else {
+            return Err(Error::DataShort(0));
+        };
+        let code = FunctionCode::new(code);
+        // 多个线圈写入: 地址 + 数量 + 字节数 + 按位打包的值
+        // 读/写多个寄存器: 读地址 + 读数量 + 写地址 + 写数量 + 字节数 + 值
+        // 读取设备标识: MEI 类型 + 读取码 + 对象
+        let odd = matches!(
+            code,
+            FunctionCode::WriteMultipleCoils
+                | FunctionCode::ReadWriteMultipleRegisters
+                | FunctionCode::ReadDeviceIdentification
+        );
+        if !odd && !data.len().is_multiple_of(2) {
+            return Err(Error::DataLenError); // 数据长度不匹配
         }
 
         let fp = Function {
-            slave: request[0],
-            code: FunctionCode::new(request[1]),
-            data_u16: result,
-            data_u8: request[2..2 + byte_count].to_vec(),
+            slave,
+            code,
+            data_u16: u8_to_u16(data),
+            data_u8: data.to_vec(),
         };
 
         Ok(fp)
     }
 
+    /// 将 RTU 请求解析为 Function，校验 CRC
+    pub fn parse_request(request: &[u8]) -> Result<Self> {
+        Self::parse_request_frame(Framing::Rtu, request)
+    }
+
     /// 生成请求数据
     pub fn request_data(&self) -> Vec<u8> {
-        let len = self.data_u16.len() * 2;
-        let mut data: Vec<u8> = Vec::with_capacity(len);
-
-        for &i in &self.data_u16 {
-            // let mut b = i.to_be_bytes().to_vec();
-            // data.append(&mut b);
-            data.push((i >> 8) as u8); // 高位字节
-            data.push(i as u8); // 低位字节
-        }
+        encode_frame(Framing::Rtu, 0, self.slave, self.request_pdu())
+    }
 
-        let mut response: Vec<u8> = vec![self.slave, self.code.value()];
-        response.extend_from_slice(&data);
+    /// 生成请求 PDU: 功能码 + 数据
+    pub fn request_pdu(&self) -> Vec<u8> {
+        let mut pdu = vec![self.code.value()];
+        pdu.extend_from_slice(&self.data_u8);
+        pdu
+    }
 
-        // 计算并添加 CRC 校验码
-        let crc = calculate_crc(&response);
-        response.push(crc as u8);
-        response.push((crc >> 8) as u8);
+    /// 写入命令期望的确认 PDU
+    ///
+    /// 多个线圈写入确认为 地址 + 数量；多个寄存器写入的请求数据为 地址 + 值，确认为 地址 + 值的个数；
+    /// 其余为请求回显
+    pub fn write_ack_pdu(&self) -> Vec<u8> {
+        match self.code {
+            FunctionCode::WriteMultipleCoils => {
+                let mut pdu = vec![self.code.value()];
+                pdu.extend(self.data_u8.iter().take(4));
+                pdu
+            }
+            FunctionCode::WriteMultipleRegisters => {
+                let address = self.data_u16.first().copied().unwrap_or_default();
+                let quantity = self.data_u16.len().saturating_sub(1) as u16;
 
-        response
+                let mut pdu = vec![self.code.value()];
+                pdu.extend(address.to_be_bytes());
+                pdu.extend(quantity.to_be_bytes());
+                pdu
+            }
+            _ => self.request_pdu(),
+        }
     }
 
-    pub fn response_data(&self) -> Vec<u8> {
+    /// 生成响应 PDU，读取为 功能码 + 字节数 + 数据，写入为确认
+    pub fn response_pdu(&self) -> Vec<u8> {
         match self.code {
             FunctionCode::ReadCoils
             | FunctionCode::ReadDiscreteInputs
             | FunctionCode::ReadHoldingRegisters
-            | FunctionCode::ReadInputRegisters => self._response_data(),
+            | FunctionCode::ReadInputRegisters
+            | FunctionCode::ReadWriteMultipleRegisters => {
+                let mut pdu = vec![self.code.value(), self.data_u8.len() as u8];
+                pdu.extend_from_slice(&self.data_u8);
+                pdu
+            }
             FunctionCode::WriteSingleCoil
-            | FunctionCode::ReadWriteMultipleRegisters
             | FunctionCode::WriteSingleRegister
             | FunctionCode::WriteMultipleCoils
             | FunctionCode::WriteMultipleRegisters
-            | FunctionCode::MaskWriteRegister
-            | FunctionCode::Custom(_) => self.request_data(),
+            | FunctionCode::MaskWriteRegister => self.write_ack_pdu(),
+            FunctionCode::Diagnostics
+            | FunctionCode::GetCommEventCounter
+            | FunctionCode::ReadDeviceIdentification
+            | FunctionCode::Custom(_) => self.request_pdu(),
         }
     }
 
-    /// 生成返回数据
-    fn _response_data(&self) -> Vec<u8> {
-        let len = self.data_u16.len() * 2;
-        let mut data: Vec<u8> = Vec::with_capacity(len);
+    /// 生成 Modbus TCP 请求
+    pub fn request_tcp(&self, transaction: u16) -> Vec<u8> {
+        encode_frame(Framing::Tcp, transaction, self.slave, self.request_pdu())
+    }
+
+    /// 生成 Modbus TCP 响应
+    pub fn response_tcp(&self, transaction: u16) -> Vec<u8> {
+        encode_frame(Framing::Tcp, transaction, self.slave, self.response_pdu())
+    }
+
+    /// 生成 Modbus ASCII 请求
+    pub fn request_ascii(&self) -> Vec<u8> {
+        encode_frame(Framing::Ascii, 0, self.slave, self.request_pdu())
+    }
+
+    /// 生成 Modbus ASCII 响应
+    pub fn response_ascii(&self) -> Vec<u8> {
+        encode_frame(Framing::Ascii, 0, self.slave, self.response_pdu())
+    }
 
-        for &i in &self.data_u16 {
-            data.push((i >> 8) as u8); // 高位字节
-            data.push(i as u8); // 低位字节
+    /// 按照帧格式生成请求，事务号仅用于 Modbus TCP
+    pub fn request_frame(&self, framing: Framing, transaction: u16) -> Vec<u8> {
+        match framing {
+            Framing::Rtu | Framing::RtuOverTcp => self.request_data(),
+            Framing::Ascii => self.request_ascii(),
+            Framing::Tcp => self.request_tcp(transaction),
         }
+    }
 
-        let mut response: Vec<u8> = vec![self.slave, self.code.value(), data.len() as u8];
-        response.extend_from_slice(&data);
+    /// 按照帧格式生成响应，事务号仅用于 Modbus TCP
+    pub fn response_frame(&self, framing: Framing, transaction: u16) -> Vec<u8> {
+        match framing {
+            Framing::Rtu | Framing::RtuOverTcp => self.response_data(),
+            Framing::Ascii => self.response_ascii(),
+            Framing::Tcp => self.response_tcp(transaction),
+        }
+    }
 
-        // 计算并添加 CRC 校验码
-        let crc = calculate_crc(&response);
-        response.push(crc as u8);
-        response.push((crc >> 8) as u8);
+    /// 生成 RTU 响应
+    pub fn response_data(&self) -> Vec<u8> {
+        encode_frame(Framing::Rtu, 0, self.slave, self.response_pdu())
+    }
+}
 
-        response
+/// 功能码最高位为 1 时为异常响应: 功能码 | 0x80 + 异常码
+fn check_exception(slave: u8, pdu: &[u8]) -> Result<()> {
+    match pdu {
+        [function, code, ..] if function & 0x80 != 0 => Err(Error::Exception {
+            slave,
+            function: FunctionCode::new(function & 0x7F),
+            code: ExceptionCode::new(*code),
+        }),
+        _ => Ok(()),
     }
 }
 
-/// 计算 Modbus RTU CRC 校验码
-pub fn calculate_crc(data: &[u8]) -> u16 {
-    let mut crc: u16 = 0xFFFF;
+fn u8_to_u16(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .collect()
+}
 
-    for &byte in data {
-        crc ^= byte as u16;
-        for _ in 0..8 {
-            if crc & 0x0001 != 0 {
-                crc >>= 1;
-                crc ^= 0xA001;
-            } else {
-                crc >>= 1;
-            }
-        }
-    }
+/// 掩码写入后的寄存器值
+pub fn mask_value(current: u16, and_mask: u16, or_mask: u16) -> u16 {
+    (current & and_mask) | (or_mask & !and_mask)
+}
 
-    crc
+/// 按位打包，第一个值为第一个字节的最低位，不足 8 位的高位补 0
+pub fn pack_bits(values: &[bool]) -> Vec<u8> {
+    values
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, &v)| byte | ((v as u8) << i))
+        })
+        .collect()
+}
+
+/// 按位解包前 `count` 个值
+pub fn unpack_bits(data: &[u8], count: usize) -> Vec<bool> {
+    (0..count.min(data.len() * 8))
+        .map(|i| data[i / 8] & (1 << (i % 8)) != 0)
+        .collect()
 }
 
 /// A Modbus function code.
@@ -284,9 +711,11 @@ pub enum FunctionCode {
     /// 写入单个寄存器: `06` (`0x06`).
     WriteSingleRegister,
 
-    // 诊断（限 serial port ) `08`（`0x08`)
-    // 获取通信事件计时器 (only serial port): `11` (`0x0B`)
-    // ----
+    /// 诊断（限 serial port ): `08` (`0x08`).
+    Diagnostics,
+    /// 获取通信事件计数器 (only serial port): `11` (`0x0B`).
+    GetCommEventCounter,
+
     /// 写入多个线圈: `15` (`0x0F`).
     WriteMultipleCoils,
     /// 写入多个寄存器: `16` (`0x10`).
@@ -298,8 +727,9 @@ pub enum FunctionCode {
     /// 读/写多个寄存器: `23` (`0x17`).
     ReadWriteMultipleRegisters,
 
-    // 读取设备标识 `43/14` (`0x2B/0x0E`)
-    // ----
+    /// 读取设备标识: `43/14` (`0x2B/0x0E`).
+    ReadDeviceIdentification,
+
     /// 自定义 Function Code.
     Custom(u8),
 }
@@ -313,12 +743,15 @@ impl FunctionCode {
             0x02 => FunctionCode::ReadDiscreteInputs,
             0x05 => FunctionCode::WriteSingleCoil,
             0x06 => FunctionCode::WriteSingleRegister,
+            0x08 => FunctionCode::Diagnostics,
+            0x0B => FunctionCode::GetCommEventCounter,
             0x03 => FunctionCode::ReadHoldingRegisters,
             0x04 => FunctionCode::ReadInputRegisters,
             0x0F => FunctionCode::WriteMultipleCoils,
             0x10 => FunctionCode::WriteMultipleRegisters,
             0x16 => FunctionCode::MaskWriteRegister,
             0x17 => FunctionCode::ReadWriteMultipleRegisters,
+            0x2B => FunctionCode::ReadDeviceIdentification,
             code => FunctionCode::Custom(code),
         }
     }
@@ -331,15 +764,39 @@ impl FunctionCode {
             FunctionCode::ReadDiscreteInputs => 0x02,
             FunctionCode::WriteSingleCoil => 0x05,
             FunctionCode::WriteSingleRegister => 0x06,
+            FunctionCode::Diagnostics => 0x08,
+            FunctionCode::GetCommEventCounter => 0x0B,
             FunctionCode::ReadHoldingRegisters => 0x03,
             FunctionCode::ReadInputRegisters => 0x04,
             FunctionCode::WriteMultipleCoils => 0x0F,
             FunctionCode::WriteMultipleRegisters => 0x10,
             FunctionCode::MaskWriteRegister => 0x16,
             FunctionCode::ReadWriteMultipleRegisters => 0x17,
+            FunctionCode::ReadDeviceIdentification => 0x2B,
             FunctionCode::Custom(code) => code,
         }
     }
+
+    /// 数据按位打包的读取命令
+    pub const fn is_bits(self) -> bool {
+        matches!(
+            self,
+            FunctionCode::ReadCoils | FunctionCode::ReadDiscreteInputs
+        )
+    }
+
+    /// 修改从站状态的写入命令
+    pub const fn is_write(self) -> bool {
+        matches!(
+            self,
+            FunctionCode::WriteSingleCoil
+                | FunctionCode::WriteSingleRegister
+                | FunctionCode::WriteMultipleCoils
+                | FunctionCode::WriteMultipleRegisters
+                | FunctionCode::MaskWriteRegister
+                | FunctionCode::ReadWriteMultipleRegisters
+        )
+    }
 }
 
 impl std::fmt::Display for FunctionCode {
@@ -348,6 +805,88 @@ impl std::fmt::Display for FunctionCode {
     }
 }
 
+impl fmt::UpperHex for FunctionCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.value(), f)
+    }
+}
+
+/// Modbus 异常码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionCode {
+    /// 非法功能: `0x01`
+    IllegalFunction,
+    /// 非法数据地址: `0x02`
+    IllegalDataAddress,
+    /// 非法数据值: `0x03`
+    IllegalDataValue,
+    /// 从站设备故障: `0x04`
+    ServerDeviceFailure,
+    /// 确认，请求已接收但需要较长时间处理: `0x05`
+    Acknowledge,
+    /// 从站设备忙: `0x06`
+    ServerDeviceBusy,
+    /// 存储奇偶校验错误: `0x08`
+    MemoryParityError,
+    /// 网关路径不可用: `0x0A`
+    GatewayPathUnavailable,
+    /// 网关目标设备无响应: `0x0B`
+    GatewayTargetDevice,
+    /// 其他异常码
+    Custom(u8),
+}
+
+impl ExceptionCode {
+    #[must_use]
+    pub const fn new(value: u8) -> Self {
+        match value {
+            0x01 => ExceptionCode::IllegalFunction,
+            0x02 => ExceptionCode::IllegalDataAddress,
+            0x03 => ExceptionCode::IllegalDataValue,
+            0x04 => ExceptionCode::ServerDeviceFailure,
+            0x05 => ExceptionCode::Acknowledge,
+            0x06 => ExceptionCode::ServerDeviceBusy,
+            0x08 => ExceptionCode::MemoryParityError,
+            0x0A => ExceptionCode::GatewayPathUnavailable,
+            0x0B => ExceptionCode::GatewayTargetDevice,
+            code => ExceptionCode::Custom(code),
+        }
+    }
+
+    #[must_use]
+    pub const fn value(self) -> u8 {
+        match self {
+            ExceptionCode::IllegalFunction => 0x01,
+            ExceptionCode::IllegalDataAddress => 0x02,
+            ExceptionCode::IllegalDataValue => 0x03,
+            ExceptionCode::ServerDeviceFailure => 0x04,
+            ExceptionCode::Acknowledge => 0x05,
+            ExceptionCode::ServerDeviceBusy => 0x06,
+            ExceptionCode::MemoryParityError => 0x08,
+            ExceptionCode::GatewayPathUnavailable => 0x0A,
+            ExceptionCode::GatewayTargetDevice => 0x0B,
+            ExceptionCode::Custom(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for ExceptionCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExceptionCode::IllegalFunction => write!(f, "非法功能"),
+            ExceptionCode::IllegalDataAddress => write!(f, "非法数据地址"),
+            ExceptionCode::IllegalDataValue => write!(f, "非法数据值"),
+            ExceptionCode::ServerDeviceFailure => write!(f, "从站设备故障"),
+            ExceptionCode::Acknowledge => write!(f, "请求已确认，正在处理"),
+            ExceptionCode::ServerDeviceBusy => write!(f, "从站设备忙"),
+            ExceptionCode::MemoryParityError => write!(f, "存储奇偶校验错误"),
+            ExceptionCode::GatewayPathUnavailable => write!(f, "网关路径不可用"),
+            ExceptionCode::GatewayTargetDevice => write!(f, "网关目标设备无响应"),
+            ExceptionCode::Custom(code) => write!(f, "未知异常 {code:#04X}"),
+        }
+    }
+}
+
 /// 获取当前串口列表
 pub fn get_ports() -> Vec<String> {
     let mut list = match serialport::available_ports() {
@@ -365,10 +904,13 @@ pub fn get_ports() -> Vec<String> {
         Err(_e) => Vec::new(),
     };
 
-    list.push("test".to_string());
+    list.push(TEST_PORT.to_string());
     list
 }
 
+/// 测试端口，连接模拟设备
+pub const TEST_PORT: &str = "test";
+
 #[cfg(not(target_os = "windows"))]
 pub fn default_port_name() -> String {
     "/dev/ttyUSB0".to_owned()
@@ -378,3 +920,215 @@ pub fn default_port_name() -> String {
 pub fn default_port_name() -> String {
     "COM1".to_owned()
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::VecDeque,
+        io::{self, Read, Write},
+        sync::atomic::AtomicUsize,
+        time::Instant,
+    };
+
+    use super::*;
+    use crate::transport::Loopback;
+
+    /// 按照分段返回数据，`None` 为一次读取超时，记录每次设定的超时
+    struct Chunked {
+        chunks: VecDeque<Option<Vec<u8>>>,
+        timeouts: Vec<Duration>,
+    }
+
+    impl Chunked {
+        fn new<I: IntoIterator<Item = Option<Vec<u8>>>>(chunks: I) -> Self {
+            Self {
+                chunks: chunks.into_iter().collect(),
+                timeouts: Vec::new(),
+            }
+        }
+
+        /// 按照 `sizes` 切分 `data`
+        fn split(data: &[u8], sizes: &[usize]) -> Self {
+            let mut rest = data;
+            let chunks = sizes.iter().map(|&n| {
+                let (chunk, tail) = rest.split_at(n);
+                rest = tail;
+                Some(chunk.to_vec())
+            });
+            Self::new(chunks)
+        }
+    }
+
+    impl Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.chunks.pop_front().flatten() {
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                None => Err(io::Error::new(io::ErrorKind::TimedOut, "无响应")),
+            }
+        }
+    }
+
+    impl Write for Chunked {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transport for Chunked {
+        fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+            self.timeouts.push(timeout);
+            Ok(())
+        }
+    }
+
+    const TIMEOUT: Duration = Duration::from_millis(300);
+    const GAP: Duration = Duration::from_millis(4);
+
+    #[test]
+    fn bits() {
+        // 10 个值占 2 个字节，第一个值为最低位
+        let values = [
+            true, false, true, true, false, false, false, false, false, true,
+        ];
+        assert_eq!(vec![0b0000_1101, 0b0000_0010], pack_bits(&values));
+        assert_eq!(values.to_vec(), unpack_bits(&pack_bits(&values), 10));
+        // 数量超过数据时截断
+        assert_eq!(8, unpack_bits(&[0xFF], 20).len());
+        assert!(pack_bits(&[]).is_empty());
+
+        assert_eq!(0b1010, mask_value(0b1000, 0b1101, 0b0010));
+    }
+
+    #[test]
+    fn rtu_frame_in_chunks() {
+        // 15 路电压电流，65 字节
+        let data = (0..30).collect();
+        let response = Function::new(0x05, FunctionCode::ReadInputRegisters, data).response_data();
+        assert_eq!(65, response.len());
+
+        let mut port = Chunked::split(&response, &[1, 3, 7, 13, 5, 9, 11, 15, 1]);
+        let frame = read_rtu_frame(&mut port, TIMEOUT, GAP).unwrap();
+        assert_eq!(response, frame);
+        // 长度已知，不等待帧间静默
+        assert!(port.timeouts.iter().all(|&t| t == TIMEOUT));
+    }
+
+    #[test]
+    fn rtu_frame_gap_cutoff() {
+        // 长度未知的功能码在帧间静默后结束，之后的数据属于下一帧
+        let mut port = Chunked::new([
+            Some(vec![0x01, 0x41]),
+            Some(vec![0x00, 0x01, 0x02]),
+            None,
+            Some(vec![0xFF]),
+        ]);
+        let frame = read_rtu_frame(&mut port, TIMEOUT, GAP).unwrap();
+        assert_eq!(vec![0x01, 0x41, 0x00, 0x01, 0x02], frame);
+        assert_eq!(vec![TIMEOUT, GAP, GAP, TIMEOUT], port.timeouts);
+        assert_eq!(1, port.chunks.len());
+
+        let mut port = Chunked::new([None]);
+        let result = read_rtu_frame(&mut port, TIMEOUT, GAP);
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+
+    /// 记录请求次数，按照 `respond` 生成响应
+    fn counted<F>(policy: Policy, respond: F) -> (Builder, Arc<AtomicUsize>)
+    where
+        F: Fn() -> Option<Vec<u8>> + Send + 'static,
+    {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let loopback = Loopback::new(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            respond()
+        });
+        let builder = Builder::with_transport("retry", loopback).policy(policy);
+        (builder, count)
+    }
+
+    fn read_request() -> FunRequest {
+        Function::new(0x01, FunctionCode::ReadHoldingRegisters, vec![0x000A, 1])
+    }
+
+    #[test]
+    fn retry_dropped_frame() {
+        let policy = Policy {
+            timeout: 10,
+            retries: 3,
+            backoff: 10,
+            ..Default::default()
+        };
+        let (builder, count) = counted(policy, || None);
+
+        let start = Instant::now();
+        let result = builder.call(&read_request());
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert_eq!(4, count.load(Ordering::SeqCst));
+        // 10 + 20 + 40
+        assert!(start.elapsed() >= Duration::from_millis(70));
+    }
+
+    #[test]
+    fn no_resend_write_on_timeout() {
+        let policy = Policy {
+            timeout: 10,
+            retries: 2,
+            backoff: 1,
+            ..Default::default()
+        };
+        let write = Function::new(0x01, FunctionCode::WriteSingleRegister, vec![0x000A, 1]);
+
+        let (builder, count) = counted(policy, || None);
+        assert!(builder.call(&write).unwrap_err().is_timeout());
+        assert_eq!(1, count.load(Ordering::SeqCst));
+
+        let policy = Policy {
+            retry_writes: true,
+            ..policy
+        };
+        let (builder, count) = counted(policy, || None);
+        assert!(builder.call(&write).unwrap_err().is_timeout());
+        assert_eq!(3, count.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn retry_busy_not_illegal_function() {
+        let policy = Policy {
+            retries: 2,
+            backoff: 1,
+            ..Default::default()
+        };
+        let exception =
+            |code: u8| move || Some(encode_frame(Framing::Rtu, 0, 0x01, vec![0x83, code]));
+
+        let (builder, count) = counted(policy, exception(0x01));
+        let result = builder.call(&read_request());
+        assert!(matches!(
+            result,
+            Err(Error::Exception {
+                code: ExceptionCode::IllegalFunction,
+                ..
+            })
+        ));
+        assert_eq!(1, count.load(Ordering::SeqCst));
+
+        let (builder, count) = counted(policy, exception(0x06));
+        let result = builder.call(&read_request());
+        assert!(matches!(
+            result,
+            Err(Error::Exception {
+                code: ExceptionCode::ServerDeviceBusy,
+                ..
+            })
+        ));
+        assert_eq!(3, count.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,450 @@
+//! 寄存器映射
+//!
+//! 用 JSON 描述设备的寄存器：名称、地址、数据类型、字序、比例、单位和读写权限，
+//! [`MapDevice`] 按照名称读写，不同厂家的控制器只需要换一份映射。
+//! ```json
+//! {
+//!   "name": "temperature",
+//!   "points": [
+//!     { "name": "temp1", "address": 10, "type": "u16", "scale": 0.1, "unit": "°C" },
+//!     { "name": "set1", "address": 60, "type": "u16", "scale": 0.1, "unit": "°C", "access": "read_write" }
+//!   ]
+//! }
+//! ```
+//! 内置映射见 [`RegisterMap::builtin`]。
+
+use std::{path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Result,
+    error::Error,
+    protocol::{Builder, FunRequest, FunResponse, Function, FunctionCode},
+    utils::current_timestamp,
+};
+
+/// 一次读取的最大寄存器数量
+const MAX_READ_WORDS: u16 = 125;
+
+/// 内置映射: 名称, JSON
+const BUILTIN: [(&str, &str); 4] = [
+    ("voltage", include_str!("../maps/voltage.json")),
+    ("temperature", include_str!("../maps/temperature.json")),
+    ("relay", include_str!("../maps/relay.json")),
+    ("power", include_str!("../maps/power.json")),
+];
+
+/// 寄存器区
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterTable {
+    /// 保持寄存器，可读写
+    #[default]
+    Holding,
+    /// 输入寄存器，只读
+    Input,
+}
+
+/// 寄存器数据类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataType {
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+}
+
+impl DataType {
+    /// 占用的寄存器数量
+    pub fn words(self) -> u16 {
+        match self {
+            DataType::U16 | DataType::I16 => 1,
+            DataType::U32 | DataType::I32 | DataType::F32 => 2,
+        }
+    }
+}
+
+/// 32 位数据的字节顺序，A 为最高字节
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum WordOrder {
+    /// 大端
+    #[default]
+    ABCD,
+    /// 字交换
+    CDAB,
+    /// 字内字节交换
+    BADC,
+    /// 小端
+    DCBA,
+}
+
+impl WordOrder {
+    pub const ALL: [WordOrder; 4] = [
+        WordOrder::ABCD,
+        WordOrder::CDAB,
+        WordOrder::BADC,
+        WordOrder::DCBA,
+    ];
+
+    /// 32 位数值转换为两个寄存器
+    pub fn to_words(self, value: u32) -> [u16; 2] {
+        let b = self.reorder(value.to_be_bytes());
+        [
+            u16::from_be_bytes([b[0], b[1]]),
+            u16::from_be_bytes([b[2], b[3]]),
+        ]
+    }
+
+    /// 两个寄存器转换为 32 位数值
+    pub fn from_words(self, words: [u16; 2]) -> u32 {
+        let [a, b] = words[0].to_be_bytes();
+        let [c, d] = words[1].to_be_bytes();
+        u32::from_be_bytes(self.reorder([a, b, c, d]))
+    }
+
+    // 四种顺序都是对合，正反转换相同
+    fn reorder(self, [a, b, c, d]: [u8; 4]) -> [u8; 4] {
+        match self {
+            WordOrder::ABCD => [a, b, c, d],
+            WordOrder::CDAB => [c, d, a, b],
+            WordOrder::BADC => [b, a, d, c],
+            WordOrder::DCBA => [d, c, b, a],
+        }
+    }
+}
+
+/// 读写权限
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    #[default]
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    pub fn readable(self) -> bool {
+        matches!(self, Access::Read | Access::ReadWrite)
+    }
+
+    pub fn writable(self) -> bool {
+        matches!(self, Access::Write | Access::ReadWrite)
+    }
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// 寄存器点，工程值 = 原始值 * `scale`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub name: String,
+    #[serde(default)]
+    pub table: RegisterTable,
+    pub address: u16,
+    #[serde(rename = "type")]
+    pub data_type: DataType,
+    /// 不设定时使用映射的字序
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_order: Option<WordOrder>,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub unit: String,
+    #[serde(default)]
+    pub access: Access,
+}
+
+impl Point {
+    /// 寄存器转换为工程值
+    pub fn decode(&self, words: &[u16], order: WordOrder) -> Result<f64> {
+        let order = self.word_order.unwrap_or(order);
+        let raw = match (self.data_type, words) {
+            (DataType::U16, [w, ..]) => *w as f64,
+            (DataType::I16, [w, ..]) => *w as i16 as f64,
+            (DataType::U32, [w0, w1, ..]) => order.from_words([*w0, *w1]) as f64,
+            (DataType::I32, [w0, w1, ..]) => order.from_words([*w0, *w1]) as i32 as f64,
+            (DataType::F32, [w0, w1, ..]) => f32::from_bits(order.from_words([*w0, *w1])) as f64,
+            _ => return Err(Error::DataShort(words.len())),
+        };
+        Ok(raw * self.scale)
+    }
+
+    /// 工程值转换为寄存器
+    pub fn encode(&self, value: f64, order: WordOrder) -> Result<Vec<u16>> {
+        let order = self.word_order.unwrap_or(order);
+        let raw = value / self.scale;
+        let out_of_range = || Error::OutOfRange {
+            point: self.name.clone(),
+            value,
+        };
+
+        let integer = |min: f64, max: f64| {
+            let raw = raw.round();
+            if raw.is_finite() && (min..=max).contains(&raw) {
+                Ok(raw)
+            } else {
+                Err(out_of_range())
+            }
+        };
+
+        Ok(match self.data_type {
+            DataType::U16 => vec![integer(0.0, u16::MAX as f64)? as u16],
+            DataType::I16 => vec![integer(i16::MIN as f64, i16::MAX as f64)? as i16 as u16],
+            DataType::U32 => order
+                .to_words(integer(0.0, u32::MAX as f64)? as u32)
+                .to_vec(),
+            DataType::I32 => order
+                .to_words(integer(i32::MIN as f64, i32::MAX as f64)? as i32 as u32)
+                .to_vec(),
+            DataType::F32 => {
+                if !raw.is_finite() {
+                    return Err(out_of_range());
+                }
+                order.to_words((raw as f32).to_bits()).to_vec()
+            }
+        })
+    }
+}
+
+/// 设备寄存器映射
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisterMap {
+    pub name: String,
+    /// 32 位数据的默认字序
+    #[serde(default)]
+    pub word_order: WordOrder,
+    /// 合并读取时允许跨过的未映射寄存器数量，默认只合并相邻的点
+    ///
+    /// 读取未映射的地址时很多控制器返回非法数据地址，确认设备支持后再设置
+    #[serde(default)]
+    pub max_gap: u16,
+    pub points: Vec<Point>,
+}
+
+impl RegisterMap {
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+    }
+
+    /// 内置映射: `voltage` `temperature` `relay` `power`
+    pub fn builtin(name: &str) -> Option<Self> {
+        BUILTIN
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, json)| Self::from_json(json).expect("内置寄存器映射格式错误"))
+    }
+
+    pub fn point(&self, name: &str) -> Result<&Point> {
+        self.points
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| Error::PointNotFound(name.to_string()))
+    }
+
+    /// 可读的点按照寄存器区和地址合并为连续的读取块，间隔超过 `max_gap` 时分开读取
+    fn read_blocks(&self) -> Vec<(RegisterTable, u16, u16, Vec<&Point>)> {
+        let mut points: Vec<&Point> = self.points.iter().filter(|p| p.access.readable()).collect();
+        points.sort_by_key(|p| (p.table, p.address));
+
+        let mut blocks: Vec<(RegisterTable, u16, u16, Vec<&Point>)> = Vec::new();
+        for point in points {
+            let end = point.address.saturating_add(point.data_type.words());
+            match blocks.last_mut() {
+                Some((table, start, len, list))
+                    if *table == point.table
+                        && point.address
+                            <= start.saturating_add(*len).saturating_add(self.max_gap)
+                        && end - *start <= MAX_READ_WORDS =>
+                {
+                    *len = (*len).max(end - *start);
+                    list.push(point);
+                }
+                _ => blocks.push((point.table, point.address, end - point.address, vec![point])),
+            }
+        }
+        blocks
+    }
+}
+
+/// 读取到的点
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointValue {
+    pub time: Duration,
+    pub slave: u8,
+    pub name: String,
+    pub value: f64,
+    pub unit: String,
+}
+
+/// 按照寄存器映射读写的通用设备
+#[derive(Debug, Clone)]
+pub struct MapDevice {
+    pub map: RegisterMap,
+    pub slave: u8,
+}
+
+impl MapDevice {
+    pub fn new(map: RegisterMap, slave: u8) -> Self {
+        Self { map, slave }
+    }
+
+    /// 读取单个点的请求
+    pub fn read_request(&self, name: &str) -> Result<FunRequest> {
+        let point = self.map.point(name)?;
+        if !point.access.readable() {
+            return Err(Error::PointAccess(point.name.clone()));
+        }
+        Ok(read_request(
+            self.slave,
+            point.table,
+            point.address,
+            point.data_type.words(),
+        ))
+    }
+
+    /// 写入单个点的请求，一个寄存器使用 0x06，两个寄存器使用 0x10
+    pub fn write_request(&self, name: &str, value: f64) -> Result<FunRequest> {
+        let point = self.map.point(name)?;
+        if !point.access.writable() || point.table == RegisterTable::Input {
+            return Err(Error::PointAccess(point.name.clone()));
+        }
+
+        let mut data = vec![point.address];
+        data.extend(point.encode(value, self.map.word_order)?);
+        let code = match data.len() {
+            2 => FunctionCode::WriteSingleRegister,
+            _ => FunctionCode::WriteMultipleRegisters,
+        };
+        Ok(Function::new(self.slave, code, data))
+    }
+
+    /// 解析单个点的读取响应
+    pub fn decode(&self, name: &str, response: &FunResponse) -> Result<PointValue> {
+        let point = self.map.point(name)?;
+        self.point_value(point, &response.data())
+    }
+
+    pub fn read(&self, builder: &Builder, name: &str) -> Result<PointValue> {
+        let response = builder.call(&self.read_request(name)?)?;
+        self.decode(name, &response)
+    }
+
+    pub fn write(&self, builder: &Builder, name: &str, value: f64) -> Result<()> {
+        let request = self.write_request(name, value)?;
+        builder.call(&request)?;
+        Ok(())
+    }
+
+    /// 读取全部可读的点，相邻的点合并为一次请求
+    pub fn read_all(&self, builder: &Builder) -> Result<Vec<PointValue>> {
+        let mut values = Vec::new();
+        for (table, start, len, points) in self.map.read_blocks() {
+            let response = builder.call(&read_request(self.slave, table, start, len))?;
+            let words = response.data();
+            for point in points {
+                let offset = (point.address - start) as usize;
+                let words = words.get(offset..).unwrap_or_default();
+                values.push(self.point_value(point, words)?);
+            }
+        }
+        Ok(values)
+    }
+
+    fn point_value(&self, point: &Point, words: &[u16]) -> Result<PointValue> {
+        Ok(PointValue {
+            time: current_timestamp(),
+            slave: self.slave,
+            name: point.name.clone(),
+            value: point.decode(words, self.map.word_order)?,
+            unit: point.unit.clone(),
+        })
+    }
+}
+
+fn read_request(slave: u8, table: RegisterTable, address: u16, words: u16) -> FunRequest {
+    let code = match table {
+        RegisterTable::Holding => FunctionCode::ReadHoldingRegisters,
+        RegisterTable::Input => FunctionCode::ReadInputRegisters,
+    };
+    Function::new(slave, code, vec![address, words])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn word_order() {
+        let value = 0x1122_3344;
+        for (order, words) in [
+            (WordOrder::ABCD, [0x1122, 0x3344]),
+            (WordOrder::CDAB, [0x3344, 0x1122]),
+            (WordOrder::BADC, [0x2211, 0x4433]),
+            (WordOrder::DCBA, [0x4433, 0x2211]),
+        ] {
+            assert_eq!(words, order.to_words(value));
+            assert_eq!(value, order.from_words(words));
+        }
+
+        let json = r#"{
+            "name": "counter",
+            "word_order": "CDAB",
+            "points": [
+                { "name": "total", "address": 0, "type": "i32", "access": "read_write" },
+                { "name": "energy", "address": 2, "type": "u32", "word_order": "ABCD" }
+            ]
+        }"#;
+        let map = RegisterMap::from_json(json).unwrap();
+        let total = map.point("total").unwrap();
+        assert_eq!(
+            vec![0xFFFE, 0xFFFF],
+            total.encode(-2.0, map.word_order).unwrap()
+        );
+        assert_eq!(
+            -2.0,
+            total.decode(&[0xFFFE, 0xFFFF], map.word_order).unwrap()
+        );
+        assert!(total.encode(3e9, map.word_order).is_err());
+        let energy = map.point("energy").unwrap();
+        assert_eq!(
+            65536.0,
+            energy.decode(&[0x0001, 0x0000], map.word_order).unwrap()
+        );
+    }
+
+    #[test]
+    fn read_blocks() {
+        let json = r#"{
+            "name": "gap",
+            "points": [
+                { "name": "a", "address": 0, "type": "u32" },
+                { "name": "b", "address": 2, "type": "u16" },
+                { "name": "c", "address": 100, "type": "u16" },
+                { "name": "d", "address": 5, "type": "u16", "table": "input" }
+            ]
+        }"#;
+        let mut map = RegisterMap::from_json(json).unwrap();
+        let blocks = |map: &RegisterMap| -> Vec<(u16, u16)> {
+            map.read_blocks()
+                .iter()
+                .map(|(_, start, len, _)| (*start, *len))
+                .collect()
+        };
+        // 相邻的点合并，中间有未映射的地址时分开
+        assert_eq!(vec![(0, 3), (100, 1), (5, 1)], blocks(&map));
+
+        map.max_gap = 97;
+        assert_eq!(vec![(0, 101), (5, 1)], blocks(&map));
+    }
+}
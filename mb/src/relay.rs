@@ -6,6 +6,7 @@ use std::time::Duration;
 /// 6-10 地址位 10 -> 6 二进制
 /// 继电器 0 ，参数二进制控制开关(8位) 0b00000000;
 use crate::{
+    device::Device,
     error::Error,
     protocol::{FunRequest, FunResponse, Function, FunctionCode},
     utils::current_timestamp,
@@ -15,15 +16,38 @@ use crate::{
 #[derive(Debug)]
 pub struct Relay;
 
-impl Relay {
+impl Device for Relay {
+    type Command = RelayMode;
+    type Output = RelayReply;
+
     /// 继电器
     /// 继电器 0 ，参数二进制控制开关(8位) 0b00000000;
-    pub fn request(slave: u8, mode: &RelayMode) -> FunRequest {
+    fn request(slave: u8, mode: &RelayMode) -> FunRequest {
         let mode = mode.params(); //(0x06, 0, 0b00000000);
-        Function::new(slave, mode.0, mode.1.to_vec())
+        Function::new(slave, mode.0, mode.1)
+    }
+
+    /// 写入的响应是请求的回显，不是开关状态
+    fn decode(mode: &RelayMode, response: FunResponse) -> crate::Result<RelayReply> {
+        Ok(match mode {
+            RelayMode::Read => RelayReply::Value(response.try_into()?),
+            RelayMode::ONOFF(_)
+            | RelayMode::ON(..)
+            | RelayMode::OFF(..)
+            | RelayMode::Switch(..) => RelayReply::Written,
+        })
     }
 }
 
+/// 按照命令区分的响应
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelayReply {
+    /// 读取的开关状态
+    Value(RelayData),
+    /// 写入已确认
+    Written,
+}
+
 #[derive(Debug)]
 pub enum RelayMode {
     /// 0b0000_0000 二进制八位占位符表示灯开关
@@ -32,22 +56,29 @@ pub enum RelayMode {
     ON(u16, u8),
     /// 第一个值为读取值，第二个值为位置
     OFF(u16, u8),
+    /// 掩码写入单个位置，不需要先读取，第一个值为位置，第二个值为开关
+    Switch(u8, bool),
     /// 读取
     Read,
 }
 
 impl RelayMode {
-    pub fn params(&self) -> (FunctionCode, [u16; 2]) {
+    pub fn params(&self) -> (FunctionCode, Vec<u16>) {
         match self {
-            RelayMode::Read => (FunctionCode::ReadHoldingRegisters, [0, 1]),
-            RelayMode::ONOFF(n) => (FunctionCode::WriteSingleRegister, [0, *n]),
+            RelayMode::Read => (FunctionCode::ReadHoldingRegisters, vec![0, 1]),
+            RelayMode::ONOFF(n) => (FunctionCode::WriteSingleRegister, vec![0, *n]),
             RelayMode::ON(value, position) => {
                 let bit = RelayData::set_bit(*value, *position, true);
-                (FunctionCode::WriteSingleRegister, [0, bit])
+                (FunctionCode::WriteSingleRegister, vec![0, bit])
             }
             RelayMode::OFF(value, position) => {
                 let bit = RelayData::set_bit(*value, *position, false);
-                (FunctionCode::WriteSingleRegister, [0, bit])
+                (FunctionCode::WriteSingleRegister, vec![0, bit])
+            }
+            RelayMode::Switch(position, state) => {
+                let bit = RelayData::set_bit(0, *position, true);
+                let or_mask = if *state { bit } else { 0 };
+                (FunctionCode::MaskWriteRegister, vec![0, !bit, or_mask])
             }
         }
     }
@@ -79,11 +110,11 @@ impl RelayData {
 }
 
 impl TryFrom<FunResponse> for RelayData {
-    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Error = crate::error::Error;
 
     fn try_from(value: FunResponse) -> std::result::Result<Self, Self::Error> {
         let data = value.data();
-        let data = data.first().ok_or(Box::new(Error::DataNull))?;
+        let data = data.first().ok_or(Error::DataNull)?;
         let dur = current_timestamp();
         let temp = RelayData {
             time: dur,
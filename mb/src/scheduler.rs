@@ -0,0 +1,536 @@
+//! 总线调度
+//!
+//! 多个设备共用一条 RS485 总线时同一时刻只能有一个请求。每条总线（见 [`Endpoint::bus`]）一个调度线程，
+//! 按照优先级取出请求（控制写入先于轮询），同一优先级在从站之间轮流，两帧之间保持帧间静默时间。
+//! 提交后返回 [`Ticket`]，可以阻塞等待或作为 `Future` 等待，也可以使用回调。
+//!
+//! 请求或回调 panic 时调度线程继续运行，该请求以 [`Error::Aborted`] 结束。
+//! [`Ticket::wait`] 超过期限后取消请求，还没有发送的请求不会再发送。
+
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, VecDeque},
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        Arc, Condvar, Mutex, OnceLock, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Result,
+    connection::lock,
+    error::Error,
+    protocol::{Builder, FunRequest, FunResponse, endpoint_gap},
+    transport::Endpoint,
+};
+
+/// [`Ticket::wait`] 在请求最长耗时之外额外等待的时间
+const WAIT_MARGIN: Duration = Duration::from_secs(1);
+
+/// 请求优先级，高优先级的请求先发送
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// 后台任务，例如设备探测
+    Background,
+    /// 周期轮询
+    Poll,
+    /// 控制写入
+    Control,
+}
+
+impl Priority {
+    /// 写入命令为控制，其余为轮询
+    pub fn of(request: &FunRequest) -> Self {
+        if request.code().is_write() {
+            Priority::Control
+        } else {
+            Priority::Poll
+        }
+    }
+}
+
+type Callback = Box<dyn FnOnce(Result<FunResponse>) + Send>;
+
+struct Job {
+    builder: Builder,
+    request: FunRequest,
+    /// 执行后取出，未执行就丢弃时以错误完成
+    done: Option<Callback>,
+    /// 等待方已放弃，发送前检查
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    fn complete(&mut self, result: Result<FunResponse>) {
+        if let Some(done) = self.done.take()
+            && let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| done(result)))
+        {
+            log::error!(
+                "从站 {} 的回调 panic: {}",
+                self.request.slave(),
+                panic_message(&*e)
+            );
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        if self.done.is_some() {
+            self.complete(Err(Error::Aborted("请求未发送".into())));
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic".into())
+}
+
+/// 同一优先级的请求，按从站轮流
+#[derive(Default)]
+struct Lane {
+    order: VecDeque<u8>,
+    jobs: HashMap<u8, VecDeque<Job>>,
+}
+
+impl Lane {
+    fn push(&mut self, job: Job) {
+        let jobs = self.jobs.entry(job.request.slave()).or_default();
+        if jobs.is_empty() {
+            self.order.push_back(job.request.slave());
+        }
+        jobs.push_back(job);
+    }
+
+    fn pop(&mut self) -> Option<Job> {
+        let slave = self.order.pop_front()?;
+        let jobs = self.jobs.get_mut(&slave)?;
+        let job = jobs.pop_front();
+        if jobs.is_empty() {
+            self.jobs.remove(&slave);
+        } else {
+            self.order.push_back(slave);
+        }
+        job
+    }
+
+    fn len(&self) -> usize {
+        self.jobs.values().map(VecDeque::len).sum()
+    }
+
+    /// 取出已取消的请求
+    fn take_cancelled(&mut self) -> Vec<Job> {
+        let mut cancelled = Vec::new();
+        for jobs in self.jobs.values_mut() {
+            let (taken, kept) = jobs.drain(..).partition(Job::is_cancelled);
+            *jobs = kept;
+            cancelled.extend(taken);
+        }
+        self.jobs.retain(|_, jobs| !jobs.is_empty());
+        let jobs = &self.jobs;
+        self.order.retain(|slave| jobs.contains_key(slave));
+        cancelled
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    lanes: BTreeMap<Priority, Lane>,
+}
+
+impl Queue {
+    fn pop(&mut self) -> Option<Job> {
+        self.lanes.values_mut().rev().find_map(Lane::pop)
+    }
+
+    fn take_cancelled(&mut self) -> Vec<Job> {
+        self.lanes
+            .values_mut()
+            .flat_map(Lane::take_cancelled)
+            .collect()
+    }
+}
+
+/// 单条总线的调度器
+pub struct Scheduler {
+    bus: String,
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Scheduler {
+    /// 获取端点所在总线的调度器，第一次使用或调度线程已退出时启动调度线程
+    pub fn get(endpoint: &Endpoint) -> Arc<Scheduler> {
+        static SCHEDULERS: OnceLock<Mutex<HashMap<String, Arc<Scheduler>>>> = OnceLock::new();
+        let mut schedulers = lock(SCHEDULERS.get_or_init(Default::default));
+
+        let bus = endpoint.bus();
+        let scheduler = schedulers
+            .entry(bus.clone())
+            .or_insert_with(|| {
+                Arc::new(Scheduler {
+                    bus,
+                    queue: Mutex::new(Queue::default()),
+                    ready: Condvar::new(),
+                    worker: Mutex::new(None),
+                })
+            })
+            .clone();
+        scheduler.start();
+        scheduler
+    }
+
+    /// 调度线程未运行时启动，队列中的请求继续处理
+    fn start(self: &Arc<Self>) {
+        let mut worker = lock(&self.worker);
+        if worker.as_ref().is_some_and(|w| !w.is_finished()) {
+            return;
+        }
+        if worker.is_some() {
+            log::error!("总线 {} 调度线程已退出，重新启动", self.bus);
+        }
+
+        let scheduler = self.clone();
+        let handle = thread::Builder::new()
+            .name(format!("mb-bus-{}", self.bus))
+            .spawn(move || scheduler.run())
+            .expect("启动总线调度线程失败");
+        *worker = Some(handle);
+    }
+
+    pub fn bus(&self) -> &str {
+        &self.bus
+    }
+
+    /// 等待发送的请求数量
+    pub fn pending(&self) -> usize {
+        lock(&self.queue).lanes.values().map(Lane::len).sum()
+    }
+
+    /// 提交请求，返回等待响应的 [`Ticket`]
+    ///
+    /// 等待期限为排在前面的请求与本请求的最长耗时，见 [`Policy::budget`](crate::policy::Policy::budget)
+    pub fn submit(
+        self: &Arc<Self>,
+        builder: &Builder,
+        request: &FunRequest,
+        priority: Priority,
+    ) -> Ticket {
+        let ahead = self.pending() as u32 + 1;
+        let ticket = Ticket::new(
+            self.clone(),
+            builder.policy.budget() * (ahead + 1) + WAIT_MARGIN,
+        );
+        let shared = ticket.shared.clone();
+        self.push(
+            builder,
+            request,
+            priority,
+            Box::new(move |result| shared.complete(result)),
+            ticket.cancelled.clone(),
+        );
+        ticket
+    }
+
+    /// 提交请求，完成后调用 `done`
+    ///
+    /// 回调在调度线程中执行，阻塞会推迟同一总线上的其他请求
+    pub fn submit_with<F>(
+        &self,
+        builder: &Builder,
+        request: &FunRequest,
+        priority: Priority,
+        done: F,
+    ) where
+        F: FnOnce(Result<FunResponse>) + Send + 'static,
+    {
+        self.push(builder, request, priority, Box::new(done), Arc::default());
+    }
+
+    fn push(
+        &self,
+        builder: &Builder,
+        request: &FunRequest,
+        priority: Priority,
+        done: Callback,
+        cancelled: Arc<AtomicBool>,
+    ) {
+        let job = Job {
+            builder: builder.clone(),
+            request: request.clone(),
+            done: Some(done),
+            cancelled,
+        };
+        lock(&self.queue)
+            .lanes
+            .entry(priority)
+            .or_default()
+            .push(job);
+        self.ready.notify_one();
+    }
+
+    /// 移除已取消的请求，在队列锁之外完成回调
+    fn remove_cancelled(&self) {
+        let cancelled = lock(&self.queue).take_cancelled();
+        drop(cancelled);
+    }
+
+    fn run(&self) {
+        let mut last: Option<(Instant, Endpoint)> = None;
+        loop {
+            let mut job = {
+                let mut queue = lock(&self.queue);
+                loop {
+                    if let Some(job) = queue.pop() {
+                        break job;
+                    }
+                    queue = self
+                        .ready
+                        .wait(queue)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            };
+
+            // 等待方已放弃的请求不再发送
+            if job.is_cancelled() {
+                continue;
+            }
+
+            // 帧间静默
+            if let Some((time, endpoint)) = &last {
+                let gap = endpoint_gap(endpoint);
+                if let Some(wait) = gap.checked_sub(time.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| job.builder.call(&job.request)))
+                .unwrap_or_else(|e| {
+                    let message = panic_message(&*e);
+                    log::error!(
+                        "{} 从站 {} 请求 panic: {message}",
+                        self.bus,
+                        job.request.slave()
+                    );
+                    Err(Error::Aborted(message))
+                });
+            last = Some((Instant::now(), job.builder.endpoint.clone()));
+            job.complete(result);
+        }
+    }
+}
+
+/// 提交请求到端点所在总线的调度器
+pub fn submit(builder: &Builder, request: &FunRequest, priority: Priority) -> Ticket {
+    Scheduler::get(&builder.endpoint).submit(builder, request, priority)
+}
+
+#[derive(Default)]
+struct Slot {
+    result: Option<Result<FunResponse>>,
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct Shared {
+    slot: Mutex<Slot>,
+    ready: Condvar,
+}
+
+impl Shared {
+    fn complete(&self, result: Result<FunResponse>) {
+        let mut slot = lock(&self.slot);
+        slot.result = Some(result);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+/// 已提交请求的响应
+pub struct Ticket {
+    shared: Arc<Shared>,
+    deadline: Instant,
+    cancelled: Arc<AtomicBool>,
+    scheduler: Arc<Scheduler>,
+}
+
+impl Ticket {
+    fn new(scheduler: Arc<Scheduler>, timeout: Duration) -> Self {
+        Self {
+            shared: Arc::default(),
+            deadline: Instant::now() + timeout,
+            cancelled: Arc::default(),
+            scheduler,
+        }
+    }
+
+    /// 取消请求，还在排队时从队列中移除，已经发送的请求无法撤回
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+        self.scheduler.remove_cancelled();
+    }
+
+    /// 阻塞等待响应，超过期限取消请求并返回 [`Error::Timeout`]
+    pub fn wait(self) -> Result<FunResponse> {
+        let mut slot = lock(&self.shared.slot);
+        loop {
+            if let Some(result) = slot.result.take() {
+                return result;
+            }
+
+            let Some(timeout) = self.deadline.checked_duration_since(Instant::now()) else {
+                drop(slot);
+                log::error!("等待总线调度超时，取消请求");
+                self.cancel();
+                return Err(Error::Timeout);
+            };
+            slot = self
+                .shared
+                .ready
+                .wait_timeout(slot, timeout)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    /// 已完成时取出响应
+    pub fn try_take(&self) -> Option<Result<FunResponse>> {
+        lock(&self.shared.slot).result.take()
+    }
+}
+
+impl Future for Ticket {
+    type Output = Result<FunResponse>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = lock(&self.shared.slot);
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::{
+        policy::Policy,
+        protocol::{Function, FunctionCode},
+        transport::Loopback,
+    };
+
+    fn read(slave: u8) -> FunRequest {
+        Function::new(slave, FunctionCode::ReadHoldingRegisters, vec![0, 1])
+    }
+
+    /// 从站 0x09 的请求 panic，其余回复一个寄存器
+    fn panicking(name: &str) -> Builder {
+        let loopback = Loopback::new(|request| {
+            assert_ne!(0x09, request[0], "模拟设备故障");
+            Some(
+                Function::new(request[0], FunctionCode::ReadHoldingRegisters, vec![1])
+                    .response_data(),
+            )
+        });
+        Builder::with_transport(name, loopback)
+    }
+
+    #[test]
+    fn survive_panic() {
+        let builder = panicking("panic-call");
+        let scheduler = Scheduler::get(&builder.endpoint);
+
+        let err = scheduler
+            .submit(&builder, &read(0x09), Priority::Poll)
+            .wait()
+            .unwrap_err();
+        assert!(matches!(err, Error::Aborted(ref message) if message.contains("模拟设备故障")));
+
+        // 回调 panic 不影响后面的请求
+        scheduler.submit_with(&builder, &read(0x01), Priority::Poll, |_| {
+            panic!("回调故障")
+        });
+        let response = scheduler
+            .submit(&builder, &read(0x01), Priority::Poll)
+            .wait();
+        assert_eq!(vec![1], response.unwrap().data());
+    }
+
+    #[test]
+    fn drop_pending_job() {
+        let (tx, rx) = mpsc::channel();
+        let job = Job {
+            builder: panicking("drop-job"),
+            request: read(0x01),
+            done: Some(Box::new(move |result| tx.send(result).unwrap())),
+            cancelled: Arc::default(),
+        };
+        drop(job);
+        assert!(matches!(rx.recv().unwrap(), Err(Error::Aborted(_))));
+    }
+
+    #[test]
+    fn wait_deadline() {
+        // 从站 0x09 的请求一直占用总线，排在后面的请求等待超时
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let (sent_tx, sent_rx) = mpsc::channel();
+        let loopback = Loopback::new(move |request| {
+            sent_tx.send(request[0]).ok();
+            if request[0] == 0x09 {
+                gate_rx.recv().ok();
+            }
+            Some(request.to_vec())
+        });
+        let policy = Policy {
+            timeout: 10,
+            retries: 0,
+            ..Default::default()
+        };
+        let builder = Builder::with_transport("deadline", loopback).policy(policy);
+        let scheduler = Scheduler::get(&builder.endpoint);
+        let write = Function::new(0x01, FunctionCode::WriteSingleRegister, vec![0, 1]);
+
+        let (done_tx, done_rx) = mpsc::channel();
+        scheduler.submit_with(&builder, &read(0x09), Priority::Poll, move |_| {
+            done_tx.send(()).unwrap()
+        });
+        assert_eq!(0x09, sent_rx.recv().unwrap());
+
+        let start = Instant::now();
+        let result = scheduler.submit(&builder, &write, Priority::Control).wait();
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert!(start.elapsed() < WAIT_MARGIN * 2);
+        assert_eq!(0, scheduler.pending());
+
+        // 总线空闲后超时的写入也不会发送
+        gate_tx.send(()).unwrap();
+        done_rx.recv().unwrap();
+        let write = Function::new(0x02, FunctionCode::WriteSingleRegister, vec![0, 1]);
+        let response = scheduler.submit(&builder, &write, Priority::Poll).wait();
+        assert!(response.is_ok());
+        assert_eq!(vec![0x02], sent_rx.try_iter().collect::<Vec<_>>());
+    }
+}
@@ -11,56 +11,185 @@
 //! let req = vec![0x01, 0x06, 0x00, 0x3C, 0x02, 0x59, 0x88, 0x9C];
 //! let res = vec![0x01, 0x06, 0x00, 0x3C, 0x02, 0x59, 0x88, 0x9C];
 //! ```
+//!
+//! 保持寄存器：
+//!
+//! | 地址 | 内容 |
+//! | --- | --- |
+//! | 10 | 温度 1 * 10 |
+//! | 14 | 温度 2 * 10 |
+//! | 46 | 按键 1 |
+//! | 47 | 按键 2 |
+//! | 60 | 设定温度 1 * 10 |
+//! | 61 | 设定温度 2 * 10 |
+//! | 63 | 运行状态，见 [`RunState`] |
+//!
+//! [`TemperatureMode::Status`] 只读取以上寄存器，相邻的地址合并为一次读取。
 
 use std::time::Duration;
 
 use crate::{
+    device::Device,
     error::Error,
     protocol::{FunRequest, FunResponse, Function, FunctionCode},
+    units::Celsius,
     utils::current_timestamp,
 };
 
 pub struct Temperature;
 
-impl Temperature {
-    pub fn request(slave: u8, mode: &TemperatureMode) -> FunRequest {
-        let mode = mode.params();
-        Function::new(slave, mode.0, mode.1.to_vec())
+impl Device for Temperature {
+    type Command = TemperatureMode;
+    type Output = TemperatureReply;
+
+    /// 状态只有第一个请求，完整请求见 [`Device::requests`]
+    fn request(slave: u8, mode: &TemperatureMode) -> FunRequest {
+        let (code, data) = mode.params()[0];
+        Function::new(slave, code, data.to_vec())
+    }
+
+    fn requests(slave: u8, mode: &TemperatureMode) -> Vec<FunRequest> {
+        mode.params()
+            .into_iter()
+            .map(|(code, data)| Function::new(slave, code, data.to_vec()))
+            .collect()
     }
+
+    fn decode(mode: &TemperatureMode, response: FunResponse) -> crate::Result<TemperatureReply> {
+        Ok(match mode {
+            TemperatureMode::Temp1 | TemperatureMode::Temp2 => {
+                TemperatureReply::Value(response.try_into()?)
+            }
+            TemperatureMode::Status => return Self::decode_all(mode, vec![response]),
+            _ => TemperatureReply::Written,
+        })
+    }
+
+    fn decode_all(
+        mode: &TemperatureMode,
+        responses: Vec<FunResponse>,
+    ) -> crate::Result<TemperatureReply> {
+        if *mode != TemperatureMode::Status {
+            let response = responses.into_iter().next().ok_or(Error::DataNull)?;
+            return Self::decode(mode, response);
+        }
+
+        // 各组按照地址放回，未读取的寄存器为 0
+        if responses.len() < STATUS_BLOCKS.len() {
+            return Err(Error::DataNull);
+        }
+        let mut data = vec![0; (STATUS_END - STATUS_ADDRESS + 1) as usize];
+        for (&(address, words), response) in STATUS_BLOCKS.iter().zip(&responses) {
+            let (offset, words) = ((address - STATUS_ADDRESS) as usize, words as usize);
+            let block = response.data();
+            if block.len() < words {
+                return Err(Error::DataShort(block.len()));
+            }
+            data[offset..offset + words].copy_from_slice(&block[..words]);
+        }
+
+        Ok(TemperatureReply::Status(TemperatureStatus::decode(&data)?))
+    }
+}
+
+/// 按照命令区分的响应
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureReply {
+    /// 单个温度
+    Value(TemperatureData),
+    /// 全部状态
+    Status(TemperatureStatus),
+    /// 写入已确认
+    Written,
 }
 
+/// 状态起始地址
+const STATUS_ADDRESS: u16 = 10;
+/// 状态的最后一个地址
+const STATUS_END: u16 = 63;
+/// 状态读取的寄存器 (起始地址, 数量)：温度 1、温度 2、按键、设定温度、运行状态
+const STATUS_BLOCKS: [(u16, u16); 5] = [(10, 1), (14, 1), (46, 2), (60, 2), (63, 1)];
+
 /// 命令请求类型
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TemperatureMode {
     /// 温度1: 获取温度 * 0.1
     Temp1,
     /// 温度2: 获取温度 * 0.1
     Temp2,
-    /// 设定温度1: 温度 * 10.0
-    Set1(u16),
-    /// 设定温度2: 温度 * 10.0
-    Set2(u16),
-    /// 运行状态: 0 停止 1 运行 2 暂停
-    Run(u16),
-    /// 按键1: 0 on 1 off
-    KeyA(u16),
-    /// 按键2: 0 on 1 off
-    KeyB(u16),
+    /// 设定温度1: 写入温度 * 10.0
+    Set1(Celsius),
+    /// 设定温度2: 写入温度 * 10.0
+    Set2(Celsius),
+    /// 运行状态
+    Run(RunState),
+    /// 按键1
+    KeyA(KeyState),
+    /// 按键2
+    KeyB(KeyState),
+    /// 读取两路温度、设定温度、运行状态和按键
+    Status,
 }
 
 impl TemperatureMode {
-    /// 获取参数 (功能, 指令, 参数)
-    pub fn params(&self) -> (FunctionCode, [u16; 2]) {
-        match self {
+    /// 获取参数 (功能, 指令, 参数)，只有 [`TemperatureMode::Status`] 有多个
+    pub fn params(&self) -> Vec<(FunctionCode, [u16; 2])> {
+        let params = match self {
             TemperatureMode::Temp1 => (FunctionCode::ReadHoldingRegisters, [10, 1]),
             TemperatureMode::Temp2 => (FunctionCode::ReadHoldingRegisters, [14, 1]),
-            TemperatureMode::Set1(n) => (FunctionCode::WriteSingleRegister, [60, *n]),
-            TemperatureMode::Set2(n) => (FunctionCode::WriteSingleRegister, [61, *n]),
-            TemperatureMode::Run(n) if *n < 3 => (FunctionCode::WriteSingleRegister, [63, *n]), // 0 1 2
-            TemperatureMode::KeyA(n) if *n < 2 => (FunctionCode::WriteSingleRegister, [46, *n]), // 0 1
-            TemperatureMode::KeyB(n) if *n < 2 => (FunctionCode::WriteSingleRegister, [47, *n]), // 0 1
-            TemperatureMode::Run(_) => (FunctionCode::WriteSingleRegister, [63, 0]), // 0 1 2
-            TemperatureMode::KeyA(_) => (FunctionCode::WriteSingleRegister, [46, 0]), // 0 1
-            TemperatureMode::KeyB(_) => (FunctionCode::WriteSingleRegister, [47, 0]), // 0 1
+            TemperatureMode::Set1(t) => (FunctionCode::WriteSingleRegister, [60, t.deci()]),
+            TemperatureMode::Set2(t) => (FunctionCode::WriteSingleRegister, [61, t.deci()]),
+            TemperatureMode::Run(n) => (FunctionCode::WriteSingleRegister, [63, *n as u16]),
+            TemperatureMode::KeyA(n) => (FunctionCode::WriteSingleRegister, [46, *n as u16]),
+            TemperatureMode::KeyB(n) => (FunctionCode::WriteSingleRegister, [47, *n as u16]),
+            TemperatureMode::Status => {
+                return STATUS_BLOCKS
+                    .iter()
+                    .map(|&(address, words)| (FunctionCode::ReadHoldingRegisters, [address, words]))
+                    .collect();
+            }
+        };
+        vec![params]
+    }
+}
+
+/// 运行状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display)]
+pub enum RunState {
+    #[default]
+    Stop = 0,
+    Run = 1,
+    Pause = 2,
+}
+
+impl TryFrom<u16> for RunState {
+    type Error = crate::error::Error;
+
+    fn try_from(value: u16) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(RunState::Stop),
+            1 => Ok(RunState::Run),
+            2 => Ok(RunState::Pause),
+            _ => Err(Error::InvalidValue { address: 63, value }),
+        }
+    }
+}
+
+/// 按键状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display)]
+pub enum KeyState {
+    #[default]
+    On = 0,
+    Off = 1,
+}
+
+impl KeyState {
+    /// 按键寄存器的值，`address` 用于错误信息
+    fn from_register(address: u16, value: u16) -> crate::Result<Self> {
+        match value {
+            0 => Ok(KeyState::On),
+            1 => Ok(KeyState::Off),
+            _ => Err(Error::InvalidValue { address, value }),
         }
     }
 }
@@ -69,25 +198,71 @@ impl TemperatureMode {
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub struct TemperatureData {
     pub time: Duration,
-    pub value: f32,
+    pub value: Celsius,
 }
 
 impl TryFrom<FunResponse> for TemperatureData {
-    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Error = crate::error::Error;
 
     fn try_from(value: FunResponse) -> std::result::Result<Self, Self::Error> {
-        let data: Vec<f32> = value
-            .data()
-            .iter()
-            .map(|&r| r as f32 * 0.1)
-            .collect::<Vec<f32>>();
-        let value = data.first().ok_or(Box::new(Error::DataNull))?;
+        let value = value.data().first().copied().ok_or(Error::DataNull)?;
 
         let dur = current_timestamp();
         let temp = TemperatureData {
             time: dur,
-            value: *value,
+            value: Celsius::from_deci(value),
         };
         Ok(temp)
     }
 }
+
+/// 温度控制器状态
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TemperatureStatus {
+    pub time: Duration,
+    /// 温度 1
+    pub temp1: Celsius,
+    /// 温度 2
+    pub temp2: Celsius,
+    /// 设定温度 1
+    pub set1: Celsius,
+    /// 设定温度 2
+    pub set2: Celsius,
+    /// 运行状态，读数无效时为空
+    pub run: Option<RunState>,
+    /// 按键 1，读数无效时为空
+    pub key_a: Option<KeyState>,
+    /// 按键 2，读数无效时为空
+    pub key_b: Option<KeyState>,
+}
+
+impl TemperatureStatus {
+    /// 解析地址 10 起的状态寄存器，运行状态和按键无效时不影响温度
+    pub fn decode(data: &[u16]) -> crate::Result<Self> {
+        if data.len() < (STATUS_END - STATUS_ADDRESS + 1) as usize {
+            return Err(Error::DataShort(data.len()));
+        }
+
+        // 按照寄存器地址取值
+        let at = |address: u16| data[(address - STATUS_ADDRESS) as usize];
+        let temp = |address: u16| Celsius::from_deci(at(address));
+
+        Ok(TemperatureStatus {
+            time: current_timestamp(),
+            temp1: temp(10),
+            temp2: temp(14),
+            set1: temp(60),
+            set2: temp(61),
+            run: valid(RunState::try_from(at(63))),
+            key_a: valid(KeyState::from_register(46, at(46))),
+            key_b: valid(KeyState::from_register(47, at(47))),
+        })
+    }
+}
+
+/// 无效的读数记录后为空
+fn valid<T>(result: crate::Result<T>) -> Option<T> {
+    result
+        .inspect_err(|e| log::warn!("温控器状态无效: {e}"))
+        .ok()
+}
@@ -0,0 +1,300 @@
+//! 传输层
+//!
+//! [`Builder`](crate::protocol::Builder) 通过 [`Transport`] 收发数据，
+//! 目前支持串口、TCP 以及内存回环（用于测试和无串口设备的机器）。
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use serialport::{ClearBuffer, SerialPort};
+
+use crate::{Result, error::Error};
+
+/// 数据传输
+pub trait Transport: Read + Write + Send {
+    /// 设定读取超时
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()>;
+
+    /// 丢弃接收缓存中残留的数据
+    fn clear_input(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for Box<dyn SerialPort> {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        SerialPort::set_timeout(self.as_mut(), timeout)?;
+        Ok(())
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.clear(ClearBuffer::Input)?;
+        Ok(())
+    }
+}
+
+/// TCP 连接
+///
+/// 部分平台读取超时返回 `WouldBlock`，这里统一为 `TimedOut`
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn new(stream: TcpStream) -> Self {
+        Self { stream }
+    }
+}
+
+impl Read for TcpTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.stream.read(buf) {
+            Ok(0) if !buf.is_empty() => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "连接已关闭",
+            )),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, e))
+            }
+            result => result,
+        }
+    }
+}
+
+impl Write for TcpTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Transport for TcpTransport {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.stream.set_read_timeout(Some(timeout))
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.stream.set_nonblocking(true)?;
+
+        let mut buffer = [0u8; 256];
+        let result = loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "连接已关闭",
+                    ));
+                }
+                Ok(_) => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.stream.set_nonblocking(false)?;
+        result
+    }
+}
+
+/// 连接目标
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    /// 串口
+    Serial { port_name: String, baudrate: u32 },
+    /// TCP `host:port`
+    Tcp { addr: String },
+    /// 自定义传输，无法重新打开
+    Custom { name: String },
+}
+
+impl Endpoint {
+    pub fn serial<T: Into<String>>(port_name: T, baudrate: u32) -> Self {
+        Endpoint::Serial {
+            port_name: port_name.into(),
+            baudrate,
+        }
+    }
+
+    pub fn tcp<T: Into<String>>(addr: T) -> Self {
+        Endpoint::Tcp { addr: addr.into() }
+    }
+
+    /// 按照端口名称解析，`tcp://host:port` 为 TCP，其余为串口
+    pub fn parse<T: AsRef<str>>(port_name: T, baudrate: u32) -> Self {
+        let port_name = port_name.as_ref();
+        match port_name.strip_prefix("tcp://") {
+            Some(addr) => Endpoint::tcp(addr),
+            None => Endpoint::serial(port_name, baudrate),
+        }
+    }
+
+    /// 物理总线，同一串口不同波特率视为同一条总线
+    pub fn bus(&self) -> String {
+        match self {
+            Endpoint::Serial { port_name, .. } => port_name.clone(),
+            endpoint => endpoint.to_string(),
+        }
+    }
+
+    /// 打开传输
+    pub fn open(&self, timeout: Duration) -> Result<Box<dyn Transport>> {
+        match self {
+            Endpoint::Serial {
+                port_name,
+                baudrate,
+            } => {
+                let port = serialport::new(port_name.clone(), *baudrate)
+                    .timeout(timeout)
+                    .open()?;
+                Ok(Box::new(port))
+            }
+            Endpoint::Tcp { addr } => {
+                let addr = addr
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "地址无法解析"))?;
+                let stream = TcpStream::connect_timeout(&addr, timeout)?;
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_nodelay(true)?;
+                Ok(Box::new(TcpTransport::new(stream)))
+            }
+            Endpoint::Custom { name } => Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotConnected,
+                format!("传输 {name} 无法重新打开"),
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Serial {
+                port_name,
+                baudrate,
+            } => write!(f, "{port_name}@{baudrate}"),
+            Endpoint::Tcp { addr } => write!(f, "tcp://{addr}"),
+            Endpoint::Custom { name } => write!(f, "{name}"),
+        }
+    }
+}
+
+type Responder = Box<dyn FnMut(&[u8]) -> Option<Vec<u8>> + Send>;
+
+/// 内存回环
+///
+/// 写入的请求在 `flush` 时交给 `responder` 处理，返回的数据作为响应读取。
+/// `responder` 返回 `None` 时视为设备无响应，读取会超时。
+pub struct Loopback {
+    responder: Responder,
+    tx: Vec<u8>,
+    rx: VecDeque<u8>,
+}
+
+impl Loopback {
+    pub fn new<F>(responder: F) -> Self
+    where
+        F: FnMut(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+    {
+        Self {
+            responder: Box::new(responder),
+            tx: Vec::new(),
+            rx: VecDeque::new(),
+        }
+    }
+}
+
+impl Read for Loopback {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rx.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "无响应"));
+        }
+
+        let n = buf.len().min(self.rx.len());
+        for (b, v) in buf.iter_mut().zip(self.rx.drain(..n)) {
+            *b = v;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for Loopback {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.tx.is_empty() {
+            return Ok(());
+        }
+
+        let request = std::mem::take(&mut self.tx);
+        if let Some(response) = (self.responder)(&request) {
+            self.rx.extend(response);
+        }
+        Ok(())
+    }
+}
+
+impl Transport for Loopback {
+    fn set_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.rx.clear();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for Loopback {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let n = buf.remaining().min(self.rx.len());
+        if n == 0 && buf.remaining() > 0 {
+            return std::task::Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "无响应")));
+        }
+
+        let data: Vec<u8> = self.rx.drain(..n).collect();
+        buf.put_slice(&data);
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for Loopback {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        std::task::Poll::Ready(self.write(buf))
+    }
+
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::task::Poll::Ready(self.flush())
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
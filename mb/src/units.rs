@@ -0,0 +1,204 @@
+//! 物理量
+//!
+//! 电压、电流、功率、温度使用各自的类型，不能直接相加或比较，单位换算必须显式调用。
+//! 序列化为数值，与原来的 `f32` 兼容。
+//! ```
+//! use mb::units::{Amps, Volts};
+//!
+//! let current = Amps::from_milli(250.0);
+//! let power = Volts::new(12.0) * current;
+//! assert_eq!("3.00W", format!("{power:.2}"));
+//!
+//! // 原来保存的整数
+//! let voltage: Volts = serde_json::from_str("220").unwrap();
+//! assert_eq!(Volts::new(220.0), voltage);
+//! ```
+
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, Neg, Sub},
+};
+
+use serde::{Deserialize, Serialize};
+
+macro_rules! quantity {
+    ($(#[$doc:meta])* $name:ident, $unit:literal) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(f32);
+
+        impl $name {
+            pub const ZERO: Self = Self(0.0);
+            /// 单位符号
+            pub const UNIT: &str = $unit;
+
+            pub const fn new(value: f32) -> Self {
+                Self(value)
+            }
+
+            /// 数值
+            pub const fn value(self) -> f32 {
+                self.0
+            }
+
+            /// 千分之一单位的数值，例如 mV mA
+            pub fn milli(self) -> f32 {
+                self.0 * 1000.0
+            }
+
+            pub fn from_milli(value: f32) -> Self {
+                Self(value / 1000.0)
+            }
+
+            pub fn abs(self) -> Self {
+                Self(self.0.abs())
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+            fn add(self, rhs: Self) -> Self {
+                Self(self.0 + rhs.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: Self) {
+                self.0 += rhs.0;
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+            fn sub(self, rhs: Self) -> Self {
+                Self(self.0 - rhs.0)
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+            fn neg(self) -> Self {
+                Self(-self.0)
+            }
+        }
+
+        /// 比例
+        impl Mul<f32> for $name {
+            type Output = Self;
+            fn mul(self, rhs: f32) -> Self {
+                Self(self.0 * rhs)
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = Self;
+            fn div(self, rhs: f32) -> Self {
+                Self(self.0 / rhs)
+            }
+        }
+
+        impl DivAssign<f32> for $name {
+            fn div_assign(&mut self, rhs: f32) {
+                self.0 /= rhs;
+            }
+        }
+
+        /// 同类物理量的比值
+        impl Div for $name {
+            type Output = f32;
+            fn div(self, rhs: Self) -> f32 {
+                self.0 / rhs.0
+            }
+        }
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                Self(iter.map(|v| v.0).sum())
+            }
+        }
+
+        /// 数值 + 单位，支持精度 `{:.2}`
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match f.precision() {
+                    Some(precision) => write!(f, "{:.*}{}", precision, self.0, $unit),
+                    None => write!(f, "{}{}", self.0, $unit),
+                }
+            }
+        }
+    };
+}
+
+quantity!(
+    /// 电压 V
+    Volts,
+    "V"
+);
+quantity!(
+    /// 电流 A
+    Amps,
+    "A"
+);
+quantity!(
+    /// 功率 W
+    Watts,
+    "W"
+);
+quantity!(
+    /// 温度 °C
+    Celsius,
+    "°C"
+);
+
+impl Mul<Amps> for Volts {
+    type Output = Watts;
+    fn mul(self, rhs: Amps) -> Watts {
+        Watts(self.0 * rhs.0)
+    }
+}
+
+impl Mul<Volts> for Amps {
+    type Output = Watts;
+    fn mul(self, rhs: Volts) -> Watts {
+        Watts(self.0 * rhs.0)
+    }
+}
+
+impl Celsius {
+    /// 温控器寄存器的值，0.1 °C
+    pub fn from_deci(value: u16) -> Self {
+        Self(value as f32 * 0.1)
+    }
+
+    /// 转换为温控器寄存器的值，0.1 °C
+    pub fn deci(self) -> u16 {
+        (self.0 * 10.0).round() as u16
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn units() {
+        assert_eq!(Watts::new(6.0), Volts::new(12.0) * Amps::new(0.5));
+        assert_eq!(Amps::new(0.25), Amps::from_milli(250.0));
+        assert_eq!(250.0, Amps::new(0.25).milli());
+        assert_eq!(2.0, Volts::new(12.0) / Volts::new(6.0));
+        assert_eq!(
+            Volts::new(6.0),
+            [Volts::new(1.0), Volts::new(2.0), Volts::new(3.0)]
+                .into_iter()
+                .sum()
+        );
+
+        // 温控器寄存器 0.1 °C
+        assert_eq!(Celsius::new(60.5), Celsius::from_deci(605));
+        assert_eq!(605, Celsius::new(60.5).deci());
+        assert_eq!("60.5°C", format!("{:.1}", Celsius::new(60.5)));
+        assert_eq!("-1V", Volts::new(-1.0).to_string());
+    }
+}
@@ -6,26 +6,193 @@ use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
+use crate::device::Device;
 use crate::error::Error;
 use crate::protocol::{FunRequest, FunResponse, Function, FunctionCode};
+use crate::units::{Amps, Volts};
 use crate::utils::current_timestamp;
 
 /// 电压电流
 /// 返回
 /// ```
-/// // 请求
+/// // 请求，默认 15 通道
 /// let request: [u8; 8] = [0x01, 0x04, 0x00, 0x00, 0x00, 0x1E, 0x70, 0x02];
 ///
 /// ```
 ///
 pub struct Voltage;
-pub const VOLTAGE_CHANNEL: usize = 15;
 
-impl Voltage {
-    pub fn request(slave: u8) -> FunRequest {
-        let mode = FunctionCode::ReadInputRegisters;
-        let params = vec![0x00, 0x1E];
-        Function::new(slave, mode, params)
+impl Device for Voltage {
+    type Command = VoltageCommand;
+    type Output = VoltageData;
+
+    fn request(slave: u8, command: &VoltageCommand) -> FunRequest {
+        let mode = command.mode.params(&command.layout);
+        Function::new(slave, mode.0, mode.1)
+    }
+
+    fn decode(command: &VoltageCommand, response: FunResponse) -> crate::Result<VoltageData> {
+        VoltageData::decode(&response, &command.layout)
+    }
+}
+
+/// 命令与模块的寄存器布局
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoltageCommand {
+    pub mode: VoltageMode,
+    pub layout: VoltageLayout,
+}
+
+impl VoltageCommand {
+    /// 布局超出一次读取的范围时返回错误
+    pub fn new(mode: VoltageMode, layout: VoltageLayout) -> crate::Result<Self> {
+        layout.validate()?;
+        Ok(Self { mode, layout })
+    }
+}
+
+/// 默认布局，15 通道
+impl From<VoltageMode> for VoltageCommand {
+    fn from(mode: VoltageMode) -> Self {
+        Self {
+            mode,
+            layout: VoltageLayout::default(),
+        }
+    }
+}
+
+/// 一次最多读取 125 个寄存器，每个通道两个
+pub const MAX_CHANNELS: usize = 62;
+
+/// 电压模块的寄存器布局
+///
+/// 从 `register_start` 开始每个通道两个输入寄存器：电压、电流，
+/// 电压 V = 原始值 * 电压比例，电流 A = 原始值 * 电流比例
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoltageLayout {
+    /// 通道数
+    pub channels: usize,
+    /// 起始寄存器
+    pub register_start: u16,
+    /// 电压比例，换算为 V
+    pub voltage_scale: f32,
+    /// 电流比例，换算为 A
+    pub current_scale: f32,
+    /// 电压显示单位
+    pub voltage_unit: VoltageUnit,
+    /// 电流显示单位
+    pub current_unit: CurrentUnit,
+}
+
+impl Default for VoltageLayout {
+    fn default() -> Self {
+        Self {
+            channels: 15,
+            register_start: 0,
+            voltage_scale: 0.001,
+            current_scale: 0.001,
+            voltage_unit: VoltageUnit::V,
+            current_unit: CurrentUnit::A,
+        }
+    }
+}
+
+impl VoltageLayout {
+    /// 寄存器数量，布局需先经过 [`VoltageLayout::validate`]
+    pub fn words(&self) -> u16 {
+        (self.channels * 2) as u16
+    }
+
+    /// 通道数为 1 ~ [`MAX_CHANNELS`]，且寄存器不超出地址范围
+    pub fn validate(&self) -> crate::Result<()> {
+        let end = self.register_start as usize + self.channels * 2;
+        if (1..=MAX_CHANNELS).contains(&self.channels) && end <= 0x10000 {
+            Ok(())
+        } else {
+            Err(Error::InvalidLayout {
+                register_start: self.register_start,
+                channels: self.channels,
+            })
+        }
+    }
+
+    /// 寄存器的值换算为电压
+    pub fn voltage(&self, raw: u16) -> Volts {
+        Volts::new(raw as f32 * self.voltage_scale)
+    }
+
+    /// 寄存器的值换算为电流
+    pub fn current(&self, raw: u16) -> Amps {
+        Amps::new(raw as f32 * self.current_scale)
+    }
+
+    /// 电压换算为寄存器的值
+    pub fn voltage_raw(&self, voltage: Volts) -> u16 {
+        (voltage.value() / self.voltage_scale).round() as u16
+    }
+
+    /// 电流换算为寄存器的值
+    pub fn current_raw(&self, current: Amps) -> u16 {
+        (current.value() / self.current_scale).round() as u16
+    }
+}
+
+/// 电压显示单位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum VoltageUnit {
+    #[default]
+    V,
+    #[serde(rename = "mV")]
+    #[strum(to_string = "mV")]
+    MilliV,
+}
+
+impl VoltageUnit {
+    /// 按照显示单位的数值
+    pub fn value(self, voltage: Volts) -> f32 {
+        match self {
+            VoltageUnit::V => voltage.value(),
+            VoltageUnit::MilliV => voltage.milli(),
+        }
+    }
+}
+
+/// 电流显示单位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum CurrentUnit {
+    #[default]
+    A,
+    #[serde(rename = "mA")]
+    #[strum(to_string = "mA")]
+    MilliA,
+}
+
+impl CurrentUnit {
+    /// 按照显示单位的数值
+    pub fn value(self, current: Amps) -> f32 {
+        match self {
+            CurrentUnit::A => current.value(),
+            CurrentUnit::MilliA => current.milli(),
+        }
+    }
+}
+
+/// 命令请求类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoltageMode {
+    /// 读取全部通道的电压电流
+    Read,
+}
+
+impl VoltageMode {
+    pub fn params(&self, layout: &VoltageLayout) -> (FunctionCode, Vec<u16>) {
+        match self {
+            VoltageMode::Read => (
+                FunctionCode::ReadInputRegisters,
+                vec![layout.register_start, layout.words()],
+            ),
+        }
     }
 }
 
@@ -46,6 +213,24 @@ impl VoltageData {
         }
     }
 
+    /// 从站无响应，全部通道为未连接
+    pub fn offline(slave: u8, channels: usize) -> Self {
+        let data = (0..channels)
+            .map(|index| VoltageChannel {
+                index,
+                ..Default::default()
+            })
+            .collect();
+        Self::new(current_timestamp(), slave, data)
+    }
+
+    /// 全部通道未连接
+    pub fn is_offline(&self) -> bool {
+        self.data
+            .iter()
+            .all(|c| c.state == VoltageState::NoConnected)
+    }
+
     pub fn set_slave(&mut self, slave: u8) {
         self.slave = slave;
     }
@@ -54,9 +239,10 @@ impl VoltageData {
         self.time = dur;
     }
 
-    pub fn update_channel_index(&mut self, index: usize) {
+    /// 第 `index` 个从站的通道编号顺延
+    pub fn update_channel_index(&mut self, index: usize, channels: usize) {
         self.data.iter_mut().for_each(|c| {
-            c.index += index * VOLTAGE_CHANNEL;
+            c.index += index * channels;
         });
     }
 
@@ -66,66 +252,133 @@ impl VoltageData {
         });
     }
 
-    pub fn voltage(&self) -> f32 {
+    /// 平均电压
+    pub fn voltage(&self) -> Volts {
         let l = self.data.len();
 
-        self.data
-            .iter()
-            .map(|c| c.voltage)
-            .fold(0_f32, |s, a| s + a)
-            / l as f32
+        self.data.iter().map(|c| c.voltage).sum::<Volts>() / l as f32
     }
 
-    pub fn current(&self) -> f32 {
+    /// 平均电流
+    pub fn current(&self) -> Amps {
         let l = self.data.len();
 
-        self.data
-            .iter()
-            .map(|c| c.current)
-            .fold(0_f32, |s, a| s + a)
-            / l as f32
+        self.data.iter().map(|c| c.current).sum::<Amps>() / l as f32
     }
 }
 
-impl TryFrom<FunResponse> for VoltageData {
-    type Error = Box<dyn std::error::Error + Send + Sync>;
-
-    fn try_from(data: FunResponse) -> std::result::Result<Self, Self::Error> {
-        let slave = data.slave();
-        let data = data.data();
+impl VoltageData {
+    /// 按照布局解析响应
+    pub fn decode(response: &FunResponse, layout: &VoltageLayout) -> crate::Result<Self> {
+        let data = response.data();
 
         if data.is_empty() {
-            return Err(Box::new(Error::DataNull));
+            return Err(Error::DataNull);
         }
 
-        if data.len() < 30 {
-            return Err(Box::new(Error::DataLenError));
+        if data.len() < layout.channels * 2 {
+            return Err(Error::DataLenError);
         }
 
-        let chs: [f32; 30] = data
-            .iter()
-            .map(|&r| r as f32)
-            .collect::<Vec<f32>>()
-            .try_into()
-            .unwrap_or([0.0; 30]);
+        let channels = data
+            .chunks_exact(2)
+            .take(layout.channels)
+            .enumerate()
+            .map(|(index, chunk)| VoltageChannel {
+                state: VoltageState::Qualified, // 默认正常
+                ..VoltageChannel::new(index, layout.voltage(chunk[0]), layout.current(chunk[1]))
+            })
+            .collect();
+
+        Ok(VoltageData::new(
+            current_timestamp(),
+            response.slave(),
+            channels,
+        ))
+    }
 
-        let mut data = VoltageData::from(chs);
-        data.set_slave(slave);
+    /// 按照布局把校准前的值转换为寄存器，与 [`VoltageData::decode`] 互逆
+    pub fn encode(&self, layout: &VoltageLayout) -> Vec<u16> {
+        let mut data = vec![0; layout.channels * 2];
+        for (i, ch) in self.data.iter().take(layout.channels).enumerate() {
+            data[i * 2] = layout.voltage_raw(ch.raw_voltage);
+            data[i * 2 + 1] = layout.current_raw(ch.raw_current);
+        }
+        data
+    }
+
+    /// 按照通道顺序校准，没有系数的通道保持原值
+    pub fn calibrate(&mut self, calibrations: &[Calibration]) {
+        self.data
+            .iter_mut()
+            .zip(calibrations)
+            .for_each(|(ch, calibration)| calibration.apply(ch));
+    }
+}
+
+/// 默认布局，15 通道
+impl TryFrom<FunResponse> for VoltageData {
+    type Error = crate::error::Error;
 
-        Ok(data)
+    fn try_from(data: FunResponse) -> std::result::Result<Self, Self::Error> {
+        VoltageData::decode(&data, &VoltageLayout::default())
     }
 }
 
 /// 电压电流
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(from = "ChannelRecord")]
 pub struct VoltageChannel {
     pub index: usize,
-    pub voltage: f32,
-    pub current: f32,
+    /// 校准后的电压
+    pub voltage: Volts,
+    /// 校准后的电流
+    pub current: Amps,
     pub state: VoltageState,
+    /// 校准前的电压
+    pub raw_voltage: Volts,
+    /// 校准前的电流
+    pub raw_current: Amps,
+}
+
+/// 保存的通道记录，旧记录没有校准前的值
+#[derive(Deserialize)]
+struct ChannelRecord {
+    index: usize,
+    voltage: Volts,
+    current: Amps,
+    state: VoltageState,
+    raw_voltage: Option<Volts>,
+    raw_current: Option<Amps>,
+}
+
+/// 没有校准前的值时记录的就是读数
+impl From<ChannelRecord> for VoltageChannel {
+    fn from(record: ChannelRecord) -> Self {
+        Self {
+            index: record.index,
+            voltage: record.voltage,
+            current: record.current,
+            state: record.state,
+            raw_voltage: record.raw_voltage.unwrap_or(record.voltage),
+            raw_current: record.raw_current.unwrap_or(record.current),
+        }
+    }
 }
 
 impl VoltageChannel {
+    /// 未校准的通道
+    pub fn new(index: usize, voltage: Volts, current: Amps) -> Self {
+        Self {
+            index,
+            voltage,
+            current,
+            raw_voltage: voltage,
+            raw_current: current,
+            ..Default::default()
+        }
+    }
+
     // 设定状态
     pub fn set_state(&mut self, verify: &Verify) {
         self.state = self.get_state(verify);
@@ -135,7 +388,7 @@ impl VoltageChannel {
     pub fn get_voltage_state(&self, verify: &Verify) -> VoltageState {
         match self.voltage {
             i if i >= verify.voltage_down && i <= verify.voltage_top => VoltageState::Qualified,
-            i if i < verify.voltage_down && i > 0. => VoltageState::UnderVoltage,
+            i if i < verify.voltage_down && i > Volts::ZERO => VoltageState::UnderVoltage,
             i if i > verify.voltage_top => VoltageState::OverVoltage,
 
             _ => VoltageState::NoOutput,
@@ -146,7 +399,7 @@ impl VoltageChannel {
     pub fn get_current_state(&self, verify: &Verify) -> VoltageState {
         match self.current {
             i if i >= verify.current_down && i <= verify.current_top => VoltageState::Qualified,
-            i if i < verify.current_down && i > 0. => VoltageState::UnderCurrent,
+            i if i < verify.current_down && i > Amps::ZERO => VoltageState::UnderCurrent,
             i if i > verify.current_top => VoltageState::OverCurrent,
 
             _ => VoltageState::NoOutput,
@@ -166,48 +419,6 @@ impl VoltageChannel {
     }
 }
 
-/// 15对 电压&电流 值
-pub type VoltageF32 = [f32; 30];
-
-impl From<VoltageF32> for VoltageData {
-    fn from(value: VoltageF32) -> Self {
-        let dur = current_timestamp();
-
-        // TODO vec ?
-        let mut ch_list: [VoltageChannel; 15] = [VoltageChannel::default(); 15];
-
-        for (i, chunk) in value.chunks(2).enumerate() {
-            if chunk.len() == 2 {
-                let ch = VoltageChannel {
-                    index: i,
-                    voltage: chunk[0] / 1000.0,     // 电压 / 1000. 单位 V
-                    current: chunk[1] / 1000.0,     // TODO 处理电流单位 mA ？
-                    state: VoltageState::Qualified, // 默认正常
-                };
-                ch_list[i] = ch;
-            }
-        }
-
-        VoltageData::new(dur, 0, ch_list.to_vec())
-    }
-}
-
-impl From<VoltageData> for VoltageF32 {
-    fn from(val: VoltageData) -> Self {
-        let mut result = [0.0; 30];
-
-        for ch in val.data.iter() {
-            let idx = ch.index;
-            if idx < 15 {
-                result[idx * 2] = ch.voltage * 1000.0;
-                result[idx * 2 + 1] = ch.current;
-            }
-        }
-
-        result
-    }
-}
-
 #[derive(
     Debug,
     Clone,
@@ -241,26 +452,186 @@ pub enum VoltageState {
     NoOutput,
 }
 
+/// 通道校准系数，校准值 = 原始值 * 增益 + 偏移
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub voltage_gain: f32,
+    pub voltage_offset: Volts,
+    pub current_gain: f32,
+    pub current_offset: Amps,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            voltage_gain: 1.0,
+            voltage_offset: Volts::ZERO,
+            current_gain: 1.0,
+            current_offset: Amps::ZERO,
+        }
+    }
+}
+
+impl Calibration {
+    /// 由原始值计算校准值，原始值保持不变
+    pub fn apply(&self, ch: &mut VoltageChannel) {
+        ch.voltage = ch.raw_voltage * self.voltage_gain + self.voltage_offset;
+        ch.current = ch.raw_current * self.current_gain + self.current_offset;
+    }
+
+    /// 最小二乘拟合 (读数, 参考值)，返回 (增益, 偏移)
+    ///
+    /// 只有一个参考值时偏移为 0；没有样本或读数为 0 时返回 `None`
+    pub fn fit(samples: &[(f32, f32)]) -> Option<(f32, f32)> {
+        let n = samples.len() as f32;
+        let mean = |f: fn(&(f32, f32)) -> f32| samples.iter().map(f).sum::<f32>() / n;
+        let (x, y) = (mean(|s| s.0), mean(|s| s.1));
+
+        let sxx: f32 = samples.iter().map(|s| (s.0 - x).powi(2)).sum();
+        let sxy: f32 = samples.iter().map(|s| (s.0 - x) * (s.1 - y)).sum();
+
+        let fit = if sxx > f32::EPSILON {
+            let gain = sxy / sxx;
+            (gain, y - gain * x)
+        } else {
+            (y / x, 0.0)
+        };
+        (fit.0.is_finite() && fit.1.is_finite()).then_some(fit)
+    }
+}
+
 /// 合格校验
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Verify {
-    /// 电压上限 V
-    pub voltage_top: f32,
+    /// 电压上限
+    pub voltage_top: Volts,
     /// 电压下限
-    pub voltage_down: f32,
-    /// 电流上限 A
-    pub current_top: f32,
+    pub voltage_down: Volts,
+    /// 电流上限
+    pub current_top: Amps,
     /// 电流下限
-    pub current_down: f32,
+    pub current_down: Amps,
 }
 
 impl Default for Verify {
     fn default() -> Self {
         Self {
-            voltage_top: 25.0,
-            voltage_down: 1.0,
-            current_top: 10.0,
-            current_down: 1.0,
+            voltage_top: Volts::new(25.0),
+            voltage_down: Volts::new(1.0),
+            current_top: Amps::new(10.0),
+            current_down: Amps::new(1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn voltage_layout() {
+        let layout = VoltageLayout {
+            channels: 8,
+            register_start: 0x10,
+            current_scale: 0.0001,
+            current_unit: CurrentUnit::MilliA,
+            ..Default::default()
+        };
+        let command = VoltageCommand::new(VoltageMode::Read, layout.clone()).unwrap();
+        assert_eq!(vec![0x10, 16], Voltage::request(0x05, &command).data());
+
+        // 解析与转换互逆
+        let channels = [(12.5, 0.025), (0.0, 0.0), (3.3, 0.1)]
+            .iter()
+            .enumerate()
+            .map(|(index, &(voltage, current))| {
+                VoltageChannel::new(index, Volts::new(voltage), Amps::new(current))
+            })
+            .collect();
+        let data = VoltageData::new(Default::default(), 0x05, channels);
+        let words = data.encode(&layout);
+        assert_eq!(16, words.len());
+        assert_eq!([12500, 250, 0, 0, 3300, 1000], words[..6]);
+
+        let response = Function::new(0x05, FunctionCode::ReadInputRegisters, words.clone());
+        let decoded = VoltageData::decode(&response, &layout).unwrap();
+        assert_eq!(words, decoded.encode(&layout));
+        let current = layout.current_unit.value(decoded.data[0].current);
+        assert!((current - 25.0).abs() < 1e-4, "{current}");
+        assert_eq!("mA", layout.current_unit.to_string());
+        assert!(VoltageData::decode(&response, &VoltageLayout::default()).is_err());
+
+        // 通道数为 0、超过一次读取的数量或寄存器越界
+        for (register_start, channels) in [(0, 0), (0, MAX_CHANNELS + 1), (0xFFF0, 15)] {
+            let layout = VoltageLayout {
+                register_start,
+                channels,
+                ..Default::default()
+            };
+            let err = VoltageCommand::new(VoltageMode::Read, layout).unwrap_err();
+            assert_eq!(ErrorKind::Invalid, err.kind());
         }
+        let layout = VoltageLayout {
+            channels: MAX_CHANNELS,
+            ..Default::default()
+        };
+        assert!(VoltageCommand::new(VoltageMode::Read, layout).is_ok());
+    }
+
+    #[test]
+    fn old_channel_record() {
+        let json = r#"{"index":3,"voltage":12.5,"current":0.25,"state":"Qualified"}"#;
+        let ch: VoltageChannel = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            (Volts::new(12.5), Amps::new(0.25)),
+            (ch.raw_voltage, ch.raw_current)
+        );
+        let data = VoltageData::new(Default::default(), 0x05, vec![ch]);
+        let layout = VoltageLayout {
+            channels: 1,
+            ..Default::default()
+        };
+        assert_eq!(vec![12500, 250], data.encode(&layout));
+
+        // 新记录保留校准前的值
+        let mut ch = VoltageChannel::new(0, Volts::new(12.0), Amps::new(1.0));
+        ch.voltage = Volts::new(12.5);
+        let ch: VoltageChannel =
+            serde_json::from_str(&serde_json::to_string(&ch).unwrap()).unwrap();
+        assert_eq!(
+            (Volts::new(12.5), Volts::new(12.0)),
+            (ch.voltage, ch.raw_voltage)
+        );
+    }
+
+    #[test]
+    fn calibration_fit() {
+        // 读数偏高 2% 且偏移 0.1
+        let samples: Vec<(f32, f32)> = [1.0, 5.0, 20.0]
+            .iter()
+            .map(|&v| (v * 1.02 + 0.1, v))
+            .collect();
+        let (gain, offset) = Calibration::fit(&samples).unwrap();
+        assert!((gain - 1.0 / 1.02).abs() < 1e-4, "{gain}");
+        assert!((offset + 0.1 / 1.02).abs() < 1e-4, "{offset}");
+
+        // 单个参考值只计算增益
+        assert_eq!(Some((0.5, 0.0)), Calibration::fit(&[(10.0, 5.0)]));
+        assert_eq!(None, Calibration::fit(&[]));
+        assert_eq!(None, Calibration::fit(&[(0.0, 5.0)]));
+
+        let calibration = Calibration {
+            voltage_gain: gain,
+            voltage_offset: Volts::new(offset),
+            ..Default::default()
+        };
+        let mut ch = VoltageChannel::new(0, Volts::new(12.0 * 1.02 + 0.1), Amps::new(1.0));
+        calibration.apply(&mut ch);
+        assert!(
+            (ch.voltage - Volts::new(12.0)).abs() < Volts::new(1e-3),
+            "{ch:?}"
+        );
+        assert_eq!(Amps::new(1.0), ch.current);
     }
 }